@@ -0,0 +1,646 @@
+use crate::core::{Instruction, PC_START_ADDRESS};
+use std::collections::HashMap;
+use std::fmt;
+
+/**
+ * An error in the assembly source, with the 1-based line it was found on
+ */
+#[derive(Debug)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::error::Error for AssemblyError {}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/**
+ * Assemble a CHIP-8 program written with the mnemonics printed by the
+ * disassembler into a ROM that starts at 0x200.
+ *
+ * Besides instructions, the source may contain:
+ *
+ *  label:              Name the current address
+ *  NAME equ EXPR       Define a constant
+ *  org EXPR            Continue assembling at another address
+ *  db EXPR, ...        Emit bytes
+ *  dw EXPR, ...        Emit big endian words
+ *  ; comment           Ignored until the end of the line
+ *
+ * The Octo syntax printed by `disasm --octo` is understood too, with
+ * `: label`, `:org`, `#` comments and bare numbers for bytes, so that
+ * either listing can be assembled again.
+ *
+ * Expressions are made of numbers (decimal, 0x hex or 0b binary), labels,
+ * constants, `+ - * /` and parentheses.
+ */
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblyError> {
+    let lines = parse(source)?;
+
+    // First pass: work out the address of every label. Sizes don't depend
+    // on the value of any expression, so forward references are fine
+    let mut symbols = HashMap::new();
+    let mut address = PC_START_ADDRESS as i64;
+
+    for line in lines.iter() {
+        if let Some(label) = &line.label {
+            define(&mut symbols, label, address, line.number)?;
+        }
+        match &line.statement {
+            Some(Statement::Equ(name, expr)) => {
+                let value = eval(expr, &symbols).map_err(|e| error(line.number, e))?;
+                define(&mut symbols, name, value, line.number)?;
+            }
+            Some(Statement::Org(expr)) => {
+                address = eval(expr, &symbols).map_err(|e| error(line.number, e))?;
+            }
+            Some(statement) => address += statement.size(),
+            None => {}
+        }
+    }
+
+    // Second pass: emit bytes
+    let mut rom: Vec<u8> = Vec::new();
+    let mut address = PC_START_ADDRESS as i64;
+
+    for line in lines.iter() {
+        let statement = match &line.statement {
+            Some(statement) => statement,
+            None => continue,
+        };
+        let eval = |expr: &str| eval(expr, &symbols).map_err(|e| error(line.number, e));
+
+        let bytes = match statement {
+            Statement::Equ(..) => continue,
+            Statement::Org(expr) => {
+                let target = eval(expr)?;
+                if target < address {
+                    return Err(error(
+                        line.number,
+                        format!("org {:#x} is behind the current address", target),
+                    ));
+                }
+                if target > u16::MAX as i64 {
+                    return Err(error(
+                        line.number,
+                        format!("org {:#x} is out of range", target),
+                    ));
+                }
+                // Fill the gap up to the new origin
+                rom.resize(rom.len() + (target - address) as usize, 0x00);
+                address = target;
+                continue;
+            }
+            Statement::Bytes(exprs) => exprs
+                .iter()
+                .map(|expr| Ok(check_range(eval(expr)?, 0xFF, "Byte", line.number)? as u8))
+                .collect::<Result<Vec<u8>, AssemblyError>>()?,
+            Statement::Words(exprs) => exprs
+                .iter()
+                .map(|expr| Ok(check_range(eval(expr)?, 0xFFFF, "Word", line.number)? as u16))
+                .collect::<Result<Vec<u16>, AssemblyError>>()?
+                .into_iter()
+                .flat_map(|word| word.to_be_bytes().to_vec())
+                .collect(),
+            Statement::Instruction(mnemonic, operands) => {
+                let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+                encode(mnemonic, &operands, &symbols)
+                    .map_err(|e| error(line.number, e))?
+                    .encode()
+            }
+        };
+
+        address += bytes.len() as i64;
+        rom.extend(bytes);
+    }
+
+    Ok(rom)
+}
+
+struct Line {
+    number: usize,
+    label: Option<String>,
+    statement: Option<Statement>,
+}
+
+enum Statement {
+    Equ(String, String),
+    Org(String),
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+    Instruction(String, Vec<String>),
+}
+
+impl Statement {
+    fn size(&self) -> i64 {
+        match self {
+            Statement::Equ(..) | Statement::Org(_) => 0,
+            Statement::Bytes(exprs) => exprs.len() as i64,
+            Statement::Words(exprs) => exprs.len() as i64 * 2,
+            // `LD I, LONG addr` is the only 4 byte instruction
+            Statement::Instruction(mnemonic, operands) => {
+                let is_long = mnemonic == "LD"
+                    && operands.len() == 2
+                    && operands[1].to_uppercase().starts_with("LONG ");
+                if is_long {
+                    4
+                } else {
+                    2
+                }
+            }
+        }
+    }
+}
+
+fn error(line: usize, message: impl Into<String>) -> AssemblyError {
+    AssemblyError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn define(
+    symbols: &mut HashMap<String, i64>,
+    name: &str,
+    value: i64,
+    line: usize,
+) -> Result<(), AssemblyError> {
+    if !is_identifier(name) || is_keyword(name) {
+        return Err(error(line, format!("`{}` is not a valid name", name)));
+    }
+    if symbols.insert(name.to_string(), value).is_some() {
+        return Err(error(line, format!("`{}` is defined more than once", name)));
+    }
+    Ok(())
+}
+
+fn check_range(value: i64, max: i64, what: &str, line: usize) -> Result<i64, AssemblyError> {
+    // Negative values are allowed and stored as two's complement
+    if value > max || value < -(max + 1) / 2 {
+        return Err(error(line, format!("{} {} is out of range", what, value)));
+    }
+    Ok(value & max)
+}
+
+/**
+ * Split the source into labels and statements, without evaluating anything
+ */
+fn parse(source: &str) -> Result<Vec<Line>, AssemblyError> {
+    let mut lines = Vec::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let number = index + 1;
+        let mut text = text.split([';', '#']).next().unwrap_or("").trim();
+        let mut label = None;
+
+        // A label may share its line with a statement, either as `label:`
+        // or Octo's `: label`
+        if let Some(rest) = text.strip_prefix(':') {
+            if rest.starts_with(char::is_whitespace) {
+                let rest = rest.trim_start();
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                label = Some(rest[..end].to_string());
+                text = rest[end..].trim();
+            }
+        } else if let Some(colon) = text.find(':') {
+            if is_identifier(&text[..colon]) {
+                label = Some(text[..colon].to_string());
+                text = text[colon + 1..].trim();
+            }
+        }
+
+        let statement = match text.is_empty() {
+            true => None,
+            false => Some(parse_statement(text).map_err(|e| error(number, e))?),
+        };
+
+        lines.push(Line {
+            number,
+            label,
+            statement,
+        });
+    }
+
+    Ok(lines)
+}
+
+fn parse_statement(text: &str) -> Result<Statement, String> {
+    if let Some(statement) = parse_octo(text) {
+        return Ok(statement);
+    }
+
+    let (first, rest) = match text.find(char::is_whitespace) {
+        Some(space) => (&text[..space], text[space..].trim()),
+        None => (text, ""),
+    };
+    let operands: Vec<String> = match rest.is_empty() {
+        true => Vec::new(),
+        false => rest
+            .split(',')
+            .map(|operand| operand.trim().to_string())
+            .collect(),
+    };
+
+    match first.to_lowercase().as_str() {
+        "org" => return Ok(Statement::Org(rest.to_string())),
+        "db" => return Ok(Statement::Bytes(operands)),
+        "dw" => return Ok(Statement::Words(operands)),
+        _ => {}
+    }
+
+    // NAME equ EXPR
+    let mut words = rest.splitn(2, char::is_whitespace);
+    if let (Some(keyword), Some(expr)) = (words.next(), words.next()) {
+        if keyword.eq_ignore_ascii_case("equ") {
+            return Ok(Statement::Equ(first.to_string(), expr.trim().to_string()));
+        }
+    }
+
+    if operands.iter().any(String::is_empty) {
+        return Err("Missing operand".to_string());
+    }
+
+    Ok(Statement::Instruction(first.to_uppercase(), operands))
+}
+
+/**
+ * Read a statement written in the Octo syntax that the disassembler
+ * prints, as the equivalent instruction or directive. Returns `None` for
+ * anything else, which is then read as the usual mnemonics
+ */
+fn parse_octo(text: &str) -> Option<Statement> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let instruction = |mnemonic: &str, operands: &[&str]| {
+        Some(Statement::Instruction(
+            mnemonic.to_string(),
+            operands.iter().map(|operand| operand.to_string()).collect(),
+        ))
+    };
+
+    // Bytes are written as bare numbers
+    if words.iter().all(|word| parse_number(word).is_ok()) {
+        let bytes = words.iter().map(|word| word.to_string()).collect();
+        return Some(Statement::Bytes(bytes));
+    }
+
+    match words.as_slice() {
+        [":org", expr @ ..] => Some(Statement::Org(expr.join(" "))),
+        ["clear"] => instruction("CLS", &[]),
+        ["return"] => instruction("RET", &[]),
+        ["scroll-down", n] => instruction("SCD", &[n]),
+        ["scroll-up", n] => instruction("SCU", &[n]),
+        ["scroll-right"] => instruction("SCR", &[]),
+        ["scroll-left"] => instruction("SCL", &[]),
+        ["lores"] => instruction("LOW", &[]),
+        ["hires"] => instruction("HIGH", &[]),
+        ["jump", nnn] => instruction("JP", &[nnn]),
+        ["jump0", nnn] => instruction("JP", &["v0", nnn]),
+        [":call", nnn] => instruction("CALL", &[nnn]),
+        // Octo gives the condition for running the next instruction,
+        // the opposite of the condition for skipping it
+        ["if", x, "!=", y, "then"] => instruction("SE", &[x, y]),
+        ["if", x, "==", y, "then"] => instruction("SNE", &[x, y]),
+        ["if", x, "-key", "then"] => instruction("SKP", &[x]),
+        ["if", x, "key", "then"] => instruction("SKNP", &[x]),
+        ["save", x, "-", y] => instruction("SAVE", &[x, y]),
+        ["load", x, "-", y] => instruction("LOAD", &[x, y]),
+        ["save", x] => instruction("LD", &["[i]", x]),
+        ["load", x] => instruction("LD", &[x, "[i]"]),
+        ["saveflags", x] => instruction("LD", &["r", x]),
+        ["loadflags", x] => instruction("LD", &[x, "r"]),
+        ["bcd", x] => instruction("LD", &["b", x]),
+        ["sprite", x, y, n] => instruction("DRW", &[x, y, n]),
+        ["i", ":=", "long", nnnn] => instruction("LD", &["i", &format!("long {}", nnnn)]),
+        ["i", ":=", "hex", x] => instruction("LD", &["f", x]),
+        ["i", ":=", "bighex", x] => instruction("LD", &["hf", x]),
+        ["i", ":=", nnn] => instruction("LD", &["i", nnn]),
+        ["i", "+=", x] => instruction("ADD", &["i", x]),
+        ["delay", ":=", x] => instruction("LD", &["dt", x]),
+        ["buzzer", ":=", x] => instruction("LD", &["st", x]),
+        ["pitch", ":=", x] => instruction("PITCH", &[x]),
+        [x, ":=", "random", nn] => instruction("RND", &[x, nn]),
+        [x, ":=", "delay"] => instruction("LD", &[x, "dt"]),
+        [x, ":=", "key"] => instruction("LD", &[x, "k"]),
+        [x, ":=", y] => instruction("LD", &[x, y]),
+        [x, "+=", y] => instruction("ADD", &[x, y]),
+        [x, "|=", y] => instruction("OR", &[x, y]),
+        [x, "&=", y] => instruction("AND", &[x, y]),
+        [x, "^=", y] => instruction("XOR", &[x, y]),
+        [x, "-=", y] => instruction("SUB", &[x, y]),
+        [x, "=-", y] => instruction("SUBN", &[x, y]),
+        [x, ">>=", y] => instruction("SHR", &[x, y]),
+        [x, "<<=", y] => instruction("SHL", &[x, y]),
+        _ => None,
+    }
+}
+
+const MNEMONICS: [&str; 31] = [
+    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE",
+    "SAVE", "LOAD", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN", "SHR", "SHL", "RND", "DRW",
+    "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+#[derive(Clone, Copy)]
+enum Operand {
+    V(u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    Long(u16),
+    Value(i64),
+}
+
+fn parse_operand(text: &str, symbols: &HashMap<String, i64>) -> Result<Operand, String> {
+    let upper = text.to_uppercase();
+    let operand = match upper.as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ => {
+            if let Some(register) = parse_register(&upper) {
+                Operand::V(register)
+            } else if let Some(expr) = upper.strip_prefix("LONG ") {
+                let value = eval(&text[text.len() - expr.len()..], symbols)?;
+                if !(0..=0xFFFF).contains(&value) {
+                    return Err(format!("Address {:#x} is out of range", value));
+                }
+                Operand::Long(value as u16)
+            } else {
+                Operand::Value(eval(text, symbols)?)
+            }
+        }
+    };
+    Ok(operand)
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix('V')?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/**
+ * Turn a mnemonic and its operands into an instruction
+ */
+fn encode(
+    mnemonic: &str,
+    operands: &[&str],
+    symbols: &HashMap<String, i64>,
+) -> Result<Instruction, String> {
+    use Instruction::*;
+    use Operand::*;
+
+    let operands = operands
+        .iter()
+        .map(|operand| parse_operand(operand, symbols))
+        .collect::<Result<Vec<Operand>, String>>()?;
+
+    let address = |value: i64| match value {
+        0..=0xFFF => Ok(value as u16),
+        _ => Err(format!("Address {:#x} is out of range", value)),
+    };
+    let byte = |value: i64| match value {
+        -0x80..=0xFF => Ok(value as u8),
+        _ => Err(format!("Byte {} is out of range", value)),
+    };
+    let nibble = |value: i64| match value {
+        0..=0xF => Ok(value as u8),
+        _ => Err(format!("{} doesn't fit in 4 bits", value)),
+    };
+
+    let instruction = match (mnemonic, operands.as_slice()) {
+        ("CLS", []) => Clear,
+        ("RET", []) => Return,
+        ("SCD", [Value(n)]) => ScrollDown(nibble(*n)?),
+        ("SCU", [Value(n)]) => ScrollUp(nibble(*n)?),
+        ("SCR", []) => ScrollRight,
+        ("SCL", []) => ScrollLeft,
+        ("EXIT", []) => Exit,
+        ("LOW", []) => LowRes,
+        ("HIGH", []) => HighRes,
+        ("JP", [Value(nnn)]) => Jump(address(*nnn)?),
+        ("JP", [V(0), Value(nnn)]) => JumpOffset(address(*nnn)?),
+        ("CALL", [Value(nnn)]) => Call(address(*nnn)?),
+        ("SE", [V(x), V(y)]) => SkipEqReg(*x, *y),
+        ("SE", [V(x), Value(nn)]) => SkipEqByte(*x, byte(*nn)?),
+        ("SNE", [V(x), V(y)]) => SkipNeReg(*x, *y),
+        ("SNE", [V(x), Value(nn)]) => SkipNeByte(*x, byte(*nn)?),
+        ("SAVE", [V(x), V(y)]) => SaveRange(*x, *y),
+        ("LOAD", [V(x), V(y)]) => LoadRange(*x, *y),
+        ("LD", [V(x), V(y)]) => LoadReg(*x, *y),
+        ("LD", [V(x), Value(nn)]) => LoadByte(*x, byte(*nn)?),
+        ("LD", [I, Value(nnn)]) => LoadI(address(*nnn)?),
+        ("LD", [I, Long(nnnn)]) => LoadILong(*nnnn),
+        ("LD", [V(x), Dt]) => GetDelay(*x),
+        ("LD", [V(x), K]) => WaitKey(*x),
+        ("LD", [Dt, V(x)]) => SetDelay(*x),
+        ("LD", [St, V(x)]) => SetSound(*x),
+        ("LD", [F, V(x)]) => Font(*x),
+        ("LD", [Hf, V(x)]) => LargeFont(*x),
+        ("LD", [B, V(x)]) => Bcd(*x),
+        ("LD", [IndirectI, V(x)]) => Store(*x),
+        ("LD", [V(x), IndirectI]) => Load(*x),
+        ("LD", [R, V(x)]) => SaveFlags(*x),
+        ("LD", [V(x), R]) => LoadFlags(*x),
+        ("ADD", [V(x), V(y)]) => AddReg(*x, *y),
+        ("ADD", [V(x), Value(nn)]) => AddByte(*x, byte(*nn)?),
+        ("ADD", [I, V(x)]) => AddI(*x),
+        ("OR", [V(x), V(y)]) => Or(*x, *y),
+        ("AND", [V(x), V(y)]) => And(*x, *y),
+        ("XOR", [V(x), V(y)]) => Xor(*x, *y),
+        ("SUB", [V(x), V(y)]) => Sub(*x, *y),
+        ("SUBN", [V(x), V(y)]) => SubN(*x, *y),
+        ("SHR", [V(x)]) => ShiftRight(*x, *x),
+        ("SHR", [V(x), V(y)]) => ShiftRight(*x, *y),
+        ("SHL", [V(x)]) => ShiftLeft(*x, *x),
+        ("SHL", [V(x), V(y)]) => ShiftLeft(*x, *y),
+        ("RND", [V(x), Value(nn)]) => Random(*x, byte(*nn)?),
+        ("DRW", [V(x), V(y), Value(n)]) => Draw(*x, *y, nibble(*n)?),
+        ("SKP", [V(x)]) => SkipKey(*x),
+        ("SKNP", [V(x)]) => SkipNotKey(*x),
+        ("PLANE", [Value(n)]) => Plane(nibble(*n)?),
+        ("AUDIO", []) => Audio,
+        ("PITCH", [V(x)]) => Pitch(*x),
+        _ if MNEMONICS.contains(&mnemonic) => {
+            return Err(format!("Invalid operands for `{}`", mnemonic))
+        }
+        _ => return Err(format!("Unknown instruction `{}`", mnemonic)),
+    };
+
+    Ok(instruction)
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_keyword(text: &str) -> bool {
+    let upper = text.to_uppercase();
+    parse_register(&upper).is_some()
+        || ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"].contains(&upper.as_str())
+}
+
+/**
+ * Evaluate an expression of numbers, symbols, `+ - * /` and parentheses
+ */
+fn eval(expr: &str, symbols: &HashMap<String, i64>) -> Result<i64, String> {
+    let tokens = tokenize(expr)?;
+    let mut parser = ExprParser {
+        tokens: &tokens,
+        pos: 0,
+        symbols,
+    };
+
+    let value = parser.sum()?;
+    if parser.pos != tokens.len() {
+        return Err(format!("Invalid expression `{}`", expr));
+    }
+    Ok(value)
+}
+
+#[derive(Clone)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(char),
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = expr.chars().collect();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if "+-*/()".contains(c) {
+            tokens.push(Token::Op(c));
+            pos += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            let word: String = chars[start..pos].iter().collect();
+            tokens.push(match c.is_ascii_digit() {
+                true => Token::Number(parse_number(&word)?),
+                false => Token::Name(word),
+            });
+        } else {
+            return Err(format!("Unexpected `{}` in expression `{}`", c, expr));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Result<i64, String> {
+    let lower = word.to_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        lower.parse()
+    };
+    parsed.map_err(|_| format!("Invalid number `{}`", word))
+}
+
+fn overflow() -> String {
+    "Expression overflows".to_string()
+}
+
+struct ExprParser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    symbols: &'a HashMap<String, i64>,
+}
+
+impl<'a> ExprParser<'a> {
+    fn next_op(&mut self, ops: &str) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(*op) => {
+                self.pos += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<i64, String> {
+        let mut value = self.product()?;
+        while let Some(op) = self.next_op("+-") {
+            let rhs = self.product()?;
+            let result = if op == '+' {
+                value.checked_add(rhs)
+            } else {
+                value.checked_sub(rhs)
+            };
+            value = result.ok_or_else(overflow)?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i64, String> {
+        let mut value = self.unary()?;
+        while let Some(op) = self.next_op("*/") {
+            let rhs = self.unary()?;
+            let result = match op {
+                '*' => value.checked_mul(rhs),
+                _ if rhs == 0 => return Err("Division by zero".to_string()),
+                _ => value.checked_div(rhs),
+            };
+            value = result.ok_or_else(overflow)?;
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.next_op("-").is_some() {
+            return self.unary()?.checked_neg().ok_or_else(overflow);
+        }
+        if self.next_op("(").is_some() {
+            let value = self.sum()?;
+            return match self.next_op(")") {
+                Some(_) => Ok(value),
+                None => Err("Missing `)`".to_string()),
+            };
+        }
+
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Name(name)) => match self.symbols.get(&name) {
+                Some(value) => Ok(*value),
+                None => Err(format!("Undefined name `{}`", name)),
+            },
+            _ => Err("Expected a value".to_string()),
+        }
+    }
+}
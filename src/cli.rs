@@ -0,0 +1,103 @@
+use crate::headless;
+use crab_chip::QuirksPreset;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: crab-chip [ROM] [OPTIONS]
+       crab-chip disasm ROM [--octo]
+       crab-chip asm SOURCE|- [-o OUTPUT]
+
+Options:
+  --config PATH          Read settings from PATH
+  --clock-hz HZ          Instructions executed per second (default: 600)
+  --scale N              Window scale: 1, 2, 4, 8, 16 or 32 (default: 8)
+  --screenshot-scale N   Scale of saved screenshots (default: 1)
+  --quirks PRESET        vip, chip-48, super-chip, xo-chip or modern (default: chip-48)
+  --extended-memory      Give the machine 64 KiB of memory
+  --mute                 Turn the sound off
+  --seed N               Seed for the random number generator (default: random, or 0 headless)
+  --movie FILE           Play back an input movie recorded with M
+  -h, --help             Show this message
+
+Headless mode:
+  --headless             Run without a window or sound, then print a hash of the screen
+  --frames N             Stop after N frames (default: 600, or the length of the movie)
+  --until-pc ADDR        Stop when PC reaches ADDR (hexadecimal)
+  --press KEY@FRAME      Hold hex KEY down during FRAME, or FRAME-FRAME
+  --output FILE          Write the final screen to FILE, as .png or .pbm
+
+Without --config, settings are read from settings.yaml in the current directory,
+or failing that from crab-chip/settings.yaml in the user's config directory.
+Options given on the command line take precedence over the settings file.";
+
+/**
+ * Everything given on the command line. Options that weren't given are
+ * `None` (or `false`), so that they don't override the settings file
+ */
+#[derive(Default)]
+pub struct Args {
+    pub rom_path: Option<PathBuf>,
+    pub config_path: Option<PathBuf>,
+    pub clock_hz: Option<u64>,
+    pub scale: Option<u8>,
+    pub screenshot_scale: Option<usize>,
+    pub quirks: Option<QuirksPreset>,
+    pub extended_memory: bool,
+    pub mute: bool,
+    pub seed: Option<u64>,
+    pub movie_path: Option<PathBuf>,
+    pub help: bool,
+    pub headless: bool,
+    pub headless_options: headless::Options,
+}
+
+pub fn parse(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    // The first headless-only option seen, to complain about if `--headless` isn't
+    let mut headless_option = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(String::as_str)
+                .ok_or(format!("Missing value for `{}`", arg))
+        };
+
+        match arg.as_str() {
+            "--config" => parsed.config_path = Some(PathBuf::from(value()?)),
+            "--clock-hz" => parsed.clock_hz = Some(parse_number(value()?, arg)?),
+            "--scale" => parsed.scale = Some(parse_number(value()?, arg)?),
+            "--screenshot-scale" => parsed.screenshot_scale = Some(parse_number(value()?, arg)?),
+            "--quirks" => parsed.quirks = Some(value()?.parse()?),
+            "--extended-memory" => parsed.extended_memory = true,
+            "--mute" => parsed.mute = true,
+            "--seed" => parsed.seed = Some(parse_number(value()?, arg)?),
+            "--movie" => parsed.movie_path = Some(PathBuf::from(value()?)),
+            "-h" | "--help" => parsed.help = true,
+            "--headless" => parsed.headless = true,
+            "--frames" | "--until-pc" | "--press" | "--output" => {
+                headless_option.get_or_insert(arg.as_str());
+                parsed.headless_options.parse_option(arg, value()?)?;
+            }
+            option if option.starts_with('-') => {
+                return Err(format!(
+                    "Unknown option `{}`. Run `crab-chip --help` for a list of options",
+                    option
+                ))
+            }
+            path => parsed.rom_path = Some(PathBuf::from(path)),
+        }
+    }
+
+    if let (Some(option), false) = (headless_option, parsed.headless) {
+        return Err(format!("`{}` can only be used with `--headless`", option));
+    }
+
+    Ok(parsed)
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str, option: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("Invalid value `{}` for `{}`", text, option))
+}
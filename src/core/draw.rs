@@ -1,50 +1,152 @@
+use super::{AccessKind, Error};
 use crate::Chip8;
-use crate::VIDEO_HEIGHT;
-use crate::VIDEO_WIDTH;
 
-const SPRITE_WIDTH: u8 = 8;
+const SPRITE_WIDTH: usize = 8;
+const LARGE_SPRITE_SIZE: usize = 16;
+
+// XO-CHIP bitplanes. Each pixel in VRAM holds one bit per plane
+const PLANES: [u8; 2] = [0b01, 0b10];
 
 /**
  * Draw sprites to Chip8 VRAM
+ *
+ * Sprites are 8 pixels wide and `n` rows tall. When `n` is 0, a
+ * SUPER-CHIP 16x16 sprite is drawn instead, made of 2 bytes per row.
+ * With more than one XO-CHIP plane selected, the sprite data for each
+ * plane follows the previous one in memory.
  */
-pub fn dxyn(chip8: &mut Chip8, vx: u8, vy: u8, n: u8, I: usize) {
-    let height = n;
+pub fn dxyn(chip8: &mut Chip8, vx: u8, vy: u8, n: u8, i: usize) -> Result<(), Error> {
+    let width = chip8.display_width();
+    let height = chip8.display_height();
+
+    let (sprite_width, sprite_height) = match n {
+        0 => (LARGE_SPRITE_SIZE, LARGE_SPRITE_SIZE),
+        n => (SPRITE_WIDTH, n as usize),
+    };
+    let bytes_per_row = sprite_width / 8;
 
-    // Wrap if going beyond screen boundaries
-    let vy = vy as usize;
-    let vx = vx as usize;
+    // The starting position always wraps around the screen
+    let vy = vy as usize % height;
+    let vx = vx as usize % width;
 
     // Set VF to 0
     chip8.registers[0xF] = 0;
 
-    // For each row of the sprite...
-    for row in 0..height {
-        let row = row as usize;
-
-        // Byte at `I` register will be drawn to the screen bit by bit
-        let sprite_byte = chip8.memory[I + row];
-
-        // For each pixel in the row of the sprite...
-        for col in 0..SPRITE_WIDTH {
-            let col = col as usize;
-            // Get the sprite pixel by looking at a specific bit of the sprite byte
-            let sprite_pixel = sprite_byte & (0x80 >> col);
-            // Get the screen pixel
-            let mut screen_pixel_index = (vy + row) * VIDEO_WIDTH + (vx + col);
-            screen_pixel_index = screen_pixel_index % (VIDEO_WIDTH * VIDEO_HEIGHT); // prevent index out of bounds
-            let screen_pixel = &mut chip8.gfx[screen_pixel_index];
-
-            // If sprite pixel is on
-            if sprite_pixel > 0 {
-                // And screen pixel also on - collision. Write to vF
-                if *screen_pixel == 0xFF {
-                    chip8.registers[0xF] = 0x01;
+    let selected_planes = chip8.selected_planes;
+    let mut sprite_address = i;
+
+    let plane_count = PLANES
+        .iter()
+        .filter(|plane| selected_planes & **plane != 0)
+        .count();
+    let sprite_len = plane_count * sprite_height * bytes_per_row;
+    chip8.log_memory_access(AccessKind::Read, i, sprite_len)?;
+
+    for plane in PLANES.iter().filter(|plane| selected_planes & **plane != 0) {
+        // For each row of the sprite...
+        for row in 0..sprite_height {
+            // Bytes at `I` register will be drawn to the screen bit by bit
+            let row_address = sprite_address + row * bytes_per_row;
+            let sprite_row = chip8.memory[row_address..row_address + bytes_per_row]
+                .iter()
+                .fold(0u16, |bits, byte| (bits << 8) | *byte as u16);
+
+            // For each pixel in the row of the sprite...
+            for col in 0..sprite_width {
+                // Get the sprite pixel by looking at a specific bit of the sprite row
+                let sprite_pixel = sprite_row & (1 << (sprite_width - 1 - col));
+
+                // Pixels that go beyond the screen boundaries are either
+                // clipped or wrapped to the other side
+                let (mut screen_x, mut screen_y) = (vx + col, vy + row);
+                if chip8.quirks.clipping {
+                    if screen_x >= width || screen_y >= height {
+                        continue;
+                    }
+                } else {
+                    screen_x %= width;
+                    screen_y %= height;
                 }
 
-                // XOR with the sprite pixel
-                *screen_pixel ^= 0xFF;
+                // Get the screen pixel
+                let screen_pixel = &mut chip8.gfx[screen_y * width + screen_x];
+
+                // If sprite pixel is on
+                if sprite_pixel > 0 {
+                    // And screen pixel also on - collision. Write to vF
+                    if *screen_pixel & plane != 0 {
+                        chip8.registers[0xF] = 0x01;
+                    }
+
+                    // XOR with the sprite pixel
+                    *screen_pixel ^= plane;
+                }
             }
         }
+
+        sprite_address += sprite_height * bytes_per_row;
+    }
+
+    chip8.draw_flag = true;
+
+    Ok(())
+}
+
+/**
+ * Clear the selected planes of the display
+ */
+pub fn clear(chip8: &mut Chip8) {
+    let planes = chip8.selected_planes;
+    chip8.gfx.iter_mut().for_each(|pixel| *pixel &= !planes);
+
+    chip8.draw_flag = true;
+}
+
+/**
+ * Scroll the selected planes of the display down by `n` pixels, filling the top with blank rows
+ */
+pub fn scroll_down(chip8: &mut Chip8, n: usize) {
+    scroll(chip8, 0, n as isize);
+}
+
+/**
+ * Scroll the selected planes of the display up by `n` pixels, filling the bottom with blank rows
+ */
+pub fn scroll_up(chip8: &mut Chip8, n: usize) {
+    scroll(chip8, 0, -(n as isize));
+}
+
+/**
+ * Scroll the selected planes of the display right by `n` pixels, filling the left edge with blank columns
+ */
+pub fn scroll_right(chip8: &mut Chip8, n: usize) {
+    scroll(chip8, n as isize, 0);
+}
+
+/**
+ * Scroll the selected planes of the display left by `n` pixels, filling the right edge with blank columns
+ */
+pub fn scroll_left(chip8: &mut Chip8, n: usize) {
+    scroll(chip8, -(n as isize), 0);
+}
+
+fn scroll(chip8: &mut Chip8, dx: isize, dy: isize) {
+    let width = chip8.display_width() as isize;
+    let height = chip8.display_height() as isize;
+    let planes = chip8.selected_planes;
+    let previous = chip8.gfx.clone();
+
+    for (i, pixel) in chip8.gfx.iter_mut().enumerate() {
+        let x = i as isize % width - dx;
+        let y = i as isize / width - dy;
+
+        // Pixels scrolled in from outside the screen are blank
+        let scrolled_in = match x >= 0 && x < width && y >= 0 && y < height {
+            true => previous[(y * width + x) as usize],
+            false => 0x00,
+        };
+
+        *pixel = (*pixel & !planes) | (scrolled_in & planes);
     }
 
     chip8.draw_flag = true;
@@ -1,8 +1,40 @@
 use std::fmt;
 
+/**
+ * Everything that can go wrong while running a program. Faults raised by
+ * an instruction carry the address and opcode of that instruction
+ */
 #[derive(Debug)]
 pub enum Error {
-    UnknownOpcode(u16),
+    UnknownOpcode {
+        pc: u16,
+        opcode: u16,
+    },
+    // 2NNN with all 16 stack slots already in use
+    StackOverflow {
+        pc: u16,
+        opcode: u16,
+    },
+    // 00EE with nothing on the stack
+    StackUnderflow {
+        pc: u16,
+        opcode: u16,
+    },
+    // An instruction read or wrote memory past the end of RAM
+    MemoryOutOfBounds {
+        address: usize,
+        pc: u16,
+        opcode: u16,
+    },
+    // The program counter left RAM, so there is no instruction to fetch
+    PcOutOfBounds {
+        pc: u16,
+    },
+    InvalidSaveState,
+    SaveStateRomMismatch,
+    InvalidMovie,
+    MovieRomMismatch,
+    RomTooLarge(usize),
 }
 
 impl std::error::Error for Error {}
@@ -10,7 +42,40 @@ impl std::error::Error for Error {}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::UnknownOpcode(op) => write!(f, "Unknown CHIP-8 opcode: {:#06x?}", op),
+            Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unknown opcode {:04x} at {:#06x}", opcode, pc)
+            }
+            Error::StackOverflow { pc, opcode } => write!(
+                f,
+                "Stack overflow: too many nested calls at {:#06x} ({:04x})",
+                pc, opcode
+            ),
+            Error::StackUnderflow { pc, opcode } => write!(
+                f,
+                "Stack underflow: return with an empty stack at {:#06x} ({:04x})",
+                pc, opcode
+            ),
+            Error::MemoryOutOfBounds {
+                address,
+                pc,
+                opcode,
+            } => write!(
+                f,
+                "Memory access out of bounds: {:#06x} by {:04x} at {:#06x}",
+                address, opcode, pc
+            ),
+            Error::PcOutOfBounds { pc } => {
+                write!(f, "Program counter out of bounds: {:#06x}", pc)
+            }
+            Error::InvalidSaveState => {
+                write!(f, "Save state is corrupt or from an incompatible version")
+            }
+            Error::SaveStateRomMismatch => write!(f, "Save state belongs to a different ROM"),
+            Error::InvalidMovie => write!(f, "Movie is corrupt or from an incompatible version"),
+            Error::MovieRomMismatch => write!(f, "Movie was recorded with a different ROM"),
+            Error::RomTooLarge(size) => {
+                write!(f, "ROM is too large to fit in memory ({} bytes)", size)
+            }
         }
     }
 }
@@ -11,7 +11,7 @@ impl std::fmt::Debug for Chip8 {
         writeln!(
             f,
             "\tMemory val at Index Register: {:#04x?}",
-            self.memory[self.index_register as usize]
+            self.memory.get(self.index_register as usize)
         )?;
 
         writeln!(f, "\tProgram Counter: {:#06x?}", self.program_counter)?;
@@ -0,0 +1,437 @@
+use std::fmt;
+
+/**
+ * A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction.
+ * `x` and `y` are register numbers, `nn` bytes and `nnn` addresses
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    // 00CN
+    ScrollDown(u8),
+    // 00DN
+    ScrollUp(u8),
+    // 00E0
+    Clear,
+    // 00EE
+    Return,
+    // 00FB
+    ScrollRight,
+    // 00FC
+    ScrollLeft,
+    // 00FD
+    Exit,
+    // 00FE
+    LowRes,
+    // 00FF
+    HighRes,
+    // 1NNN
+    Jump(u16),
+    // 2NNN
+    Call(u16),
+    // 3XNN
+    SkipEqByte(u8, u8),
+    // 4XNN
+    SkipNeByte(u8, u8),
+    // 5XY0
+    SkipEqReg(u8, u8),
+    // 5XY2
+    SaveRange(u8, u8),
+    // 5XY3
+    LoadRange(u8, u8),
+    // 6XNN
+    LoadByte(u8, u8),
+    // 7XNN
+    AddByte(u8, u8),
+    // 8XY0
+    LoadReg(u8, u8),
+    // 8XY1
+    Or(u8, u8),
+    // 8XY2
+    And(u8, u8),
+    // 8XY3
+    Xor(u8, u8),
+    // 8XY4
+    AddReg(u8, u8),
+    // 8XY5
+    Sub(u8, u8),
+    // 8XY6
+    ShiftRight(u8, u8),
+    // 8XY7
+    SubN(u8, u8),
+    // 8XYE
+    ShiftLeft(u8, u8),
+    // 9XY0
+    SkipNeReg(u8, u8),
+    // ANNN
+    LoadI(u16),
+    // BNNN
+    JumpOffset(u16),
+    // CXNN
+    Random(u8, u8),
+    // DXYN
+    Draw(u8, u8, u8),
+    // EX9E
+    SkipKey(u8),
+    // EXA1
+    SkipNotKey(u8),
+    // F000 NNNN
+    LoadILong(u16),
+    // FN01
+    Plane(u8),
+    // F002
+    Audio,
+    // FX07
+    GetDelay(u8),
+    // FX0A
+    WaitKey(u8),
+    // FX15
+    SetDelay(u8),
+    // FX18
+    SetSound(u8),
+    // FX1E
+    AddI(u8),
+    // FX29
+    Font(u8),
+    // FX30
+    LargeFont(u8),
+    // FX33
+    Bcd(u8),
+    // FX3A
+    Pitch(u8),
+    // FX55
+    Store(u8),
+    // FX65
+    Load(u8),
+    // FX75
+    SaveFlags(u8),
+    // FX85
+    LoadFlags(u8),
+}
+
+/**
+ * Assembly language flavours that instructions can be printed in
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    // Mnemonics from Cowgod's Chip-8 Technical Reference, e.g. `LD V0, 0x05`
+    Cowgod,
+    // Octo's high level syntax, e.g. `v0 := 0x05`
+    Octo,
+}
+
+impl Instruction {
+    /**
+     * Decode an opcode. `next` is the word following the opcode in memory,
+     * which is only used by XO-CHIP's 4 byte F000 NNNN
+     */
+    pub fn decode(opcode: u16, next: u16) -> Option<Instruction> {
+        use Instruction::*;
+
+        let nnn = opcode & 0x0FFF;
+        let nn = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+
+        let instruction = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00C0..=0x00CF => ScrollDown(n),
+                0x00D0..=0x00DF => ScrollUp(n),
+                0x00E0 => Clear,
+                0x00EE => Return,
+                0x00FB => ScrollRight,
+                0x00FC => ScrollLeft,
+                0x00FD => Exit,
+                0x00FE => LowRes,
+                0x00FF => HighRes,
+                _ => return None,
+            },
+            0x1000 => Jump(nnn),
+            0x2000 => Call(nnn),
+            0x3000 => SkipEqByte(x, nn),
+            0x4000 => SkipNeByte(x, nn),
+            0x5000 => match n {
+                0x0 => SkipEqReg(x, y),
+                0x2 => SaveRange(x, y),
+                0x3 => LoadRange(x, y),
+                _ => return None,
+            },
+            0x6000 => LoadByte(x, nn),
+            0x7000 => AddByte(x, nn),
+            0x8000 => match n {
+                0x0 => LoadReg(x, y),
+                0x1 => Or(x, y),
+                0x2 => And(x, y),
+                0x3 => Xor(x, y),
+                0x4 => AddReg(x, y),
+                0x5 => Sub(x, y),
+                0x6 => ShiftRight(x, y),
+                0x7 => SubN(x, y),
+                0xE => ShiftLeft(x, y),
+                _ => return None,
+            },
+            0x9000 => match n {
+                0x0 => SkipNeReg(x, y),
+                _ => return None,
+            },
+            0xA000 => LoadI(nnn),
+            0xB000 => JumpOffset(nnn),
+            0xC000 => Random(x, nn),
+            0xD000 => Draw(x, y, n),
+            0xE000 => match nn {
+                0x9E => SkipKey(x),
+                0xA1 => SkipNotKey(x),
+                _ => return None,
+            },
+            0xF000 => match nn {
+                0x00 if x == 0 => LoadILong(next),
+                0x01 => Plane(x),
+                0x02 if x == 0 => Audio,
+                0x07 => GetDelay(x),
+                0x0A => WaitKey(x),
+                0x15 => SetDelay(x),
+                0x18 => SetSound(x),
+                0x1E => AddI(x),
+                0x29 => Font(x),
+                0x30 => LargeFont(x),
+                0x33 => Bcd(x),
+                0x3A => Pitch(x),
+                0x55 => Store(x),
+                0x65 => Load(x),
+                0x75 => SaveFlags(x),
+                0x85 => LoadFlags(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /**
+     * Encode the instruction back into its opcode bytes
+     */
+    pub fn encode(&self) -> Vec<u8> {
+        use Instruction::*;
+
+        let xy = |base: u16, x: u8, y: u8, n: u16| base | (x as u16) << 8 | (y as u16) << 4 | n;
+        let xnn = |base: u16, x: u8, nn: u8| base | (x as u16) << 8 | nn as u16;
+        let fx = |x: u8, nn: u16| 0xF000 | (x as u16) << 8 | nn;
+
+        let opcode = match *self {
+            ScrollDown(n) => 0x00C0 | n as u16,
+            ScrollUp(n) => 0x00D0 | n as u16,
+            Clear => 0x00E0,
+            Return => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            Jump(nnn) => 0x1000 | nnn,
+            Call(nnn) => 0x2000 | nnn,
+            SkipEqByte(x, nn) => xnn(0x3000, x, nn),
+            SkipNeByte(x, nn) => xnn(0x4000, x, nn),
+            SkipEqReg(x, y) => xy(0x5000, x, y, 0x0),
+            SaveRange(x, y) => xy(0x5000, x, y, 0x2),
+            LoadRange(x, y) => xy(0x5000, x, y, 0x3),
+            LoadByte(x, nn) => xnn(0x6000, x, nn),
+            AddByte(x, nn) => xnn(0x7000, x, nn),
+            LoadReg(x, y) => xy(0x8000, x, y, 0x0),
+            Or(x, y) => xy(0x8000, x, y, 0x1),
+            And(x, y) => xy(0x8000, x, y, 0x2),
+            Xor(x, y) => xy(0x8000, x, y, 0x3),
+            AddReg(x, y) => xy(0x8000, x, y, 0x4),
+            Sub(x, y) => xy(0x8000, x, y, 0x5),
+            ShiftRight(x, y) => xy(0x8000, x, y, 0x6),
+            SubN(x, y) => xy(0x8000, x, y, 0x7),
+            ShiftLeft(x, y) => xy(0x8000, x, y, 0xE),
+            SkipNeReg(x, y) => xy(0x9000, x, y, 0x0),
+            LoadI(nnn) => 0xA000 | nnn,
+            JumpOffset(nnn) => 0xB000 | nnn,
+            Random(x, nn) => xnn(0xC000, x, nn),
+            Draw(x, y, n) => xy(0xD000, x, y, n as u16),
+            SkipKey(x) => xnn(0xE000, x, 0x9E),
+            SkipNotKey(x) => xnn(0xE000, x, 0xA1),
+            LoadILong(nnnn) => return vec![0xF0, 0x00, (nnnn >> 8) as u8, nnnn as u8],
+            Plane(n) => fx(n, 0x01),
+            Audio => 0xF002,
+            GetDelay(x) => fx(x, 0x07),
+            WaitKey(x) => fx(x, 0x0A),
+            SetDelay(x) => fx(x, 0x15),
+            SetSound(x) => fx(x, 0x18),
+            AddI(x) => fx(x, 0x1E),
+            Font(x) => fx(x, 0x29),
+            LargeFont(x) => fx(x, 0x30),
+            Bcd(x) => fx(x, 0x33),
+            Pitch(x) => fx(x, 0x3A),
+            Store(x) => fx(x, 0x55),
+            Load(x) => fx(x, 0x65),
+            SaveFlags(x) => fx(x, 0x75),
+            LoadFlags(x) => fx(x, 0x85),
+        };
+
+        opcode.to_be_bytes().to_vec()
+    }
+
+    /**
+     * Size of the instruction in bytes
+     */
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LoadILong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /**
+     * Whether the instruction conditionally skips the one after it
+     */
+    pub fn is_skip(&self) -> bool {
+        use Instruction::*;
+        matches!(
+            self,
+            SkipEqByte(..)
+                | SkipNeByte(..)
+                | SkipEqReg(..)
+                | SkipNeReg(..)
+                | SkipKey(_)
+                | SkipNotKey(_)
+        )
+    }
+
+    /**
+     * Format the instruction in the given syntax. Jump and call targets
+     * (and I addresses) are passed through `address` so they can be
+     * replaced with label names
+     */
+    pub fn format(&self, syntax: Syntax, address: &dyn Fn(u16) -> String) -> String {
+        match syntax {
+            Syntax::Cowgod => self.format_cowgod(address),
+            Syntax::Octo => self.format_octo(address),
+        }
+    }
+
+    fn format_cowgod(&self, address: &dyn Fn(u16) -> String) -> String {
+        use Instruction::*;
+
+        match *self {
+            ScrollDown(n) => format!("SCD {}", n),
+            ScrollUp(n) => format!("SCU {}", n),
+            Clear => "CLS".to_string(),
+            Return => "RET".to_string(),
+            ScrollRight => "SCR".to_string(),
+            ScrollLeft => "SCL".to_string(),
+            Exit => "EXIT".to_string(),
+            LowRes => "LOW".to_string(),
+            HighRes => "HIGH".to_string(),
+            Jump(nnn) => format!("JP {}", address(nnn)),
+            Call(nnn) => format!("CALL {}", address(nnn)),
+            SkipEqByte(x, nn) => format!("SE V{:X}, {:#04x}", x, nn),
+            SkipNeByte(x, nn) => format!("SNE V{:X}, {:#04x}", x, nn),
+            SkipEqReg(x, y) => format!("SE V{:X}, V{:X}", x, y),
+            SaveRange(x, y) => format!("SAVE V{:X}, V{:X}", x, y),
+            LoadRange(x, y) => format!("LOAD V{:X}, V{:X}", x, y),
+            LoadByte(x, nn) => format!("LD V{:X}, {:#04x}", x, nn),
+            AddByte(x, nn) => format!("ADD V{:X}, {:#04x}", x, nn),
+            LoadReg(x, y) => format!("LD V{:X}, V{:X}", x, y),
+            Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+            And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+            AddReg(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+            ShiftRight(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+            SubN(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+            SkipNeReg(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+            LoadI(nnn) => format!("LD I, {}", address(nnn)),
+            JumpOffset(nnn) => format!("JP V0, {}", address(nnn)),
+            Random(x, nn) => format!("RND V{:X}, {:#04x}", x, nn),
+            Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipKey(x) => format!("SKP V{:X}", x),
+            SkipNotKey(x) => format!("SKNP V{:X}", x),
+            LoadILong(nnnn) => format!("LD I, LONG {}", address(nnnn)),
+            Plane(n) => format!("PLANE {}", n),
+            Audio => "AUDIO".to_string(),
+            GetDelay(x) => format!("LD V{:X}, DT", x),
+            WaitKey(x) => format!("LD V{:X}, K", x),
+            SetDelay(x) => format!("LD DT, V{:X}", x),
+            SetSound(x) => format!("LD ST, V{:X}", x),
+            AddI(x) => format!("ADD I, V{:X}", x),
+            Font(x) => format!("LD F, V{:X}", x),
+            LargeFont(x) => format!("LD HF, V{:X}", x),
+            Bcd(x) => format!("LD B, V{:X}", x),
+            Pitch(x) => format!("PITCH V{:X}", x),
+            Store(x) => format!("LD [I], V{:X}", x),
+            Load(x) => format!("LD V{:X}, [I]", x),
+            SaveFlags(x) => format!("LD R, V{:X}", x),
+            LoadFlags(x) => format!("LD V{:X}, R", x),
+        }
+    }
+
+    fn format_octo(&self, address: &dyn Fn(u16) -> String) -> String {
+        use Instruction::*;
+
+        // Octo expresses skips as the condition under which the
+        // following instruction runs, which is the opposite of the skip
+        match *self {
+            ScrollDown(n) => format!("scroll-down {}", n),
+            ScrollUp(n) => format!("scroll-up {}", n),
+            Clear => "clear".to_string(),
+            Return => "return".to_string(),
+            ScrollRight => "scroll-right".to_string(),
+            ScrollLeft => "scroll-left".to_string(),
+            Exit => "exit".to_string(),
+            LowRes => "lores".to_string(),
+            HighRes => "hires".to_string(),
+            Jump(nnn) => format!("jump {}", address(nnn)),
+            Call(nnn) => format!(":call {}", address(nnn)),
+            SkipEqByte(x, nn) => format!("if v{:x} != {:#04x} then", x, nn),
+            SkipNeByte(x, nn) => format!("if v{:x} == {:#04x} then", x, nn),
+            SkipEqReg(x, y) => format!("if v{:x} != v{:x} then", x, y),
+            SaveRange(x, y) => format!("save v{:x} - v{:x}", x, y),
+            LoadRange(x, y) => format!("load v{:x} - v{:x}", x, y),
+            LoadByte(x, nn) => format!("v{:x} := {:#04x}", x, nn),
+            AddByte(x, nn) => format!("v{:x} += {:#04x}", x, nn),
+            LoadReg(x, y) => format!("v{:x} := v{:x}", x, y),
+            Or(x, y) => format!("v{:x} |= v{:x}", x, y),
+            And(x, y) => format!("v{:x} &= v{:x}", x, y),
+            Xor(x, y) => format!("v{:x} ^= v{:x}", x, y),
+            AddReg(x, y) => format!("v{:x} += v{:x}", x, y),
+            Sub(x, y) => format!("v{:x} -= v{:x}", x, y),
+            ShiftRight(x, y) => format!("v{:x} >>= v{:x}", x, y),
+            SubN(x, y) => format!("v{:x} =- v{:x}", x, y),
+            ShiftLeft(x, y) => format!("v{:x} <<= v{:x}", x, y),
+            SkipNeReg(x, y) => format!("if v{:x} == v{:x} then", x, y),
+            LoadI(nnn) => format!("i := {}", address(nnn)),
+            JumpOffset(nnn) => format!("jump0 {}", address(nnn)),
+            Random(x, nn) => format!("v{:x} := random {:#04x}", x, nn),
+            Draw(x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+            SkipKey(x) => format!("if v{:x} -key then", x),
+            SkipNotKey(x) => format!("if v{:x} key then", x),
+            LoadILong(nnnn) => format!("i := long {}", address(nnnn)),
+            Plane(n) => format!("plane {}", n),
+            Audio => "audio".to_string(),
+            GetDelay(x) => format!("v{:x} := delay", x),
+            WaitKey(x) => format!("v{:x} := key", x),
+            SetDelay(x) => format!("delay := v{:x}", x),
+            SetSound(x) => format!("buzzer := v{:x}", x),
+            AddI(x) => format!("i += v{:x}", x),
+            Font(x) => format!("i := hex v{:x}", x),
+            LargeFont(x) => format!("i := bighex v{:x}", x),
+            Bcd(x) => format!("bcd v{:x}", x),
+            Pitch(x) => format!("pitch := v{:x}", x),
+            Store(x) => format!("save v{:x}", x),
+            Load(x) => format!("load v{:x}", x),
+            SaveFlags(x) => format!("saveflags v{:x}", x),
+            LoadFlags(x) => format!("loadflags v{:x}", x),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let address = |address: u16| format!("{:#05x}", address);
+        write!(f, "{}", self.format(Syntax::Cowgod, &address))
+    }
+}
@@ -2,31 +2,113 @@ mod draw;
 mod error;
 mod fmt_debug;
 mod fontset;
+mod instruction;
+mod movie;
+mod quirks;
+mod random;
+mod rewind;
+mod state;
+mod timing;
 use crate::CLOCK_SPEED_HZ;
+use crate::HIRES_VIDEO_HEIGHT;
+use crate::HIRES_VIDEO_WIDTH;
+use crate::TARGET_FPS;
 use crate::VIDEO_HEIGHT;
 use crate::VIDEO_WIDTH;
-use error::Error;
+pub use error::Error;
+pub use instruction::{Instruction, Syntax};
+pub use movie::Movie;
+pub use quirks::{Quirks, QuirksPreset};
 use rand::Rng;
-use std::fs::File;
-use std::io;
-use std::io::Read;
+pub use random::{RandomMode, RandomSource, StdRandom, VipRandom};
+pub use rewind::RewindBuffer;
+pub use timing::Timing;
+use timing::VIP_CYCLES_PER_FRAME;
 
+pub const MEMORY_SIZE: usize = 4096;
+pub const XO_CHIP_MEMORY_SIZE: usize = 65536;
+// Far beyond anything playable, but small enough that frames can't overflow
+const MAX_CLOCK_SPEED_HZ: u64 = 10_000_000;
 const FONTSET_START_ADDRESS: u16 = 0x50;
-const PC_START_ADDRESS: u16 = 0x200;
+const LARGE_FONTSET_START_ADDRESS: u16 = 0xA0;
+pub const PC_START_ADDRESS: u16 = 0x200;
 
-// Chip8 timers decrement at 60hz, even though the clock speed may be higher
-const CYCLES_PER_TIMER_DECREMENT: usize = CLOCK_SPEED_HZ as usize / 60;
+/**
+ * How much memory the machine has
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemorySize {
+    // 4 KiB, as on every platform before XO-CHIP
+    Standard,
+    // XO-CHIP's 64 KiB
+    Extended,
+}
+
+impl MemorySize {
+    pub fn bytes(self) -> usize {
+        match self {
+            MemorySize::Standard => MEMORY_SIZE,
+            MemorySize::Extended => XO_CHIP_MEMORY_SIZE,
+        }
+    }
+    /**
+     * The memory size with this many bytes, if it's one of the two
+     */
+    pub fn from_bytes(bytes: usize) -> Option<Self> {
+        match bytes {
+            MEMORY_SIZE => Some(MemorySize::Standard),
+            XO_CHIP_MEMORY_SIZE => Some(MemorySize::Extended),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * A range of memory read or written by an opcode, excluding instruction fetches
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub address: usize,
+    pub len: usize,
+}
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/**
+ * Progress of an Fx0A instruction waiting for a key
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum KeyWait {
+    // Not waiting
+    Idle,
+    // Waiting for a key to be pressed, other than the ones that already
+    // had been this frame when the wait started
+    Press(u16),
+    // Waiting for this key to be released
+    Release(u8),
+}
+
+#[derive(Clone)]
 pub struct Chip8 {
     opcode: u16,
-    memory: [u8; 4096],
+    // 4 KiB, or 64 KiB for XO-CHIP programs
+    memory: Vec<u8>,
     // Registers v0, v1 ... vF
     registers: [u8; 16],
     index_register: u16,
     // PC
     program_counter: u16,
-    // Video RAM
-    pub gfx: [u8; VIDEO_WIDTH * VIDEO_HEIGHT],
+    // Video RAM, sized for the current display resolution
+    gfx: Vec<u8>,
+    // SUPER-CHIP 128x64 high resolution mode
+    hires: bool,
+    // XO-CHIP bitplanes that drawing opcodes operate on
+    selected_planes: u8,
     // Timers
     delay_timer: u8,
     sound_timer: u8,
@@ -35,36 +117,95 @@ pub struct Chip8 {
     stack_pointer: u8,
     // Input
     keypad: [bool; 16],
+    // Keys that have gone down and come up since the last vertical blank,
+    // one bit per key
+    key_presses: u16,
+    key_releases: u16,
+    // How far Fx0A has got with waiting for a key
+    key_wait: KeyWait,
+    // SUPER-CHIP "RPL" user flags
+    rpl_flags: [u8; 16],
+    // XO-CHIP audio
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    // Interpreter-specific opcode behaviour
+    quirks: Quirks,
+    // SHA-1 of the loaded ROM, used to match save states to their game
+    rom_hash: [u8; 20],
+    // Source of CXNN's random numbers, and the seed it started from. The
+    // mode is `None` when the source was supplied from outside
+    random: Box<dyn RandomSource>,
+    random_mode: Option<RandomMode>,
+    rng_seed: u64,
 
     /* === Non-standard === */
     pub draw_flag: bool,
-    pub sound_flag: bool,
-    timer_loop: u16,
+    pub exit_flag: bool,
+    // Raised at every vertical blank, and lowered when a DXYN starts
+    // waiting for the next one
+    vblank: bool,
+    // Whether a DXYN is waiting for the vertical blank, with the display wait quirk
+    display_waiting: bool,
+    // Instructions executed per second
+    clock_speed: u64,
+    // Instructions owed to the next frame, when the clock speed
+    // doesn't divide evenly into frames. In 1/TARGET_FPS of a cycle
+    cycle_remainder: u64,
+    timing: Timing,
+    // Cycles left to run in this frame, in instructions or VIP machine
+    // cycles. Negative when the last instruction ran past the end
+    frame_cycles: i64,
+    // Memory touched by the last cycle, for debugger watchpoints
+    memory_accesses: Vec<MemoryAccess>,
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks, memory_size: MemorySize) -> Self {
+        // Every run is different unless a seed is chosen with `set_rng_seed`
+        let rng_seed = rand::thread_rng().gen();
+        let mut random = RandomMode::default().source();
+        random.seed(rng_seed);
+
         Chip8 {
             opcode: 0x0000,
-            memory: [0x00; 4096],
+            memory: vec![0x00; memory_size.bytes()],
             registers: [0x00; 16],
             index_register: 0x00,
             program_counter: 0x00,
-            gfx: [0x00; VIDEO_WIDTH * VIDEO_HEIGHT],
+            gfx: vec![0x00; VIDEO_WIDTH * VIDEO_HEIGHT],
+            hires: false,
+            selected_planes: 0b01,
             delay_timer: 0x00,
             sound_timer: 0x00,
             stack: [0x00; 16],
             stack_pointer: 0x00,
             keypad: [false; 16],
+            key_presses: 0,
+            key_releases: 0,
+            key_wait: KeyWait::Idle,
+            rpl_flags: [0x00; 16],
+            audio_pattern: None,
+            pitch: 64,
+            quirks,
+            rom_hash: [0x00; 20],
+            random,
+            random_mode: Some(RandomMode::default()),
+            rng_seed,
             draw_flag: false,
-            sound_flag: false,
-            timer_loop: 0x0000,
+            exit_flag: false,
+            vblank: false,
+            display_waiting: false,
+            clock_speed: CLOCK_SPEED_HZ,
+            cycle_remainder: 0,
+            timing: Timing::default(),
+            frame_cycles: 0,
+            memory_accesses: Vec::new(),
         }
     }
     pub fn initialize(&mut self) {
         self.program_counter = PC_START_ADDRESS;
 
-        // Load fontset into memory
+        // Load fontsets into memory
         let font = fontset::get();
         let addr = FONTSET_START_ADDRESS as usize;
         let mem_slice = &mut self.memory[addr..];
@@ -72,37 +213,416 @@ impl Chip8 {
         for (mem_byte, font_byte) in mem_slice.iter_mut().zip(font.iter()) {
             *mem_byte = *font_byte;
         }
+
+        let large_font = fontset::get_large();
+        let addr = LARGE_FONTSET_START_ADDRESS as usize;
+        let mem_slice = &mut self.memory[addr..];
+
+        for (mem_byte, font_byte) in mem_slice.iter_mut().zip(large_font.iter()) {
+            *mem_byte = *font_byte;
+        }
     }
-    pub fn load_game(&mut self, file_name: &str) -> Result<(), io::Error> {
-        let file_path = format!("roms/{}", file_name);
-        let f = File::open(file_path)?;
+    /**
+     * Put the machine back the way it was when first switched on, with the
+     * same quirks, memory size, clock speed and random numbers. The ROM
+     * has to be loaded again afterwards
+     */
+    pub fn reset(&mut self) {
+        let mut chip8 = Chip8::new(self.quirks, self.memory_size());
+        chip8.clock_speed = self.clock_speed;
+        chip8.timing = self.timing;
+        chip8.random = self.random.clone();
+        chip8.random_mode = self.random_mode;
+        chip8.set_rng_seed(self.rng_seed);
+        chip8.initialize();
+        *self = chip8;
+    }
+    /**
+     * Copy a ROM image into memory at the program start address
+     */
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Error> {
+        let pc = PC_START_ADDRESS as usize;
+        if rom.len() > self.memory.len() - pc {
+            return Err(Error::RomTooLarge(rom.len()));
+        }
 
-        let pc = self.program_counter as usize;
-        let mem_slice = &mut self.memory[pc..];
+        self.rom_hash = sha1_smol::Sha1::from(rom).digest().bytes();
+        self.memory[pc..pc + rom.len()].copy_from_slice(rom);
 
-        for (mem_byte, file_byte) in mem_slice.iter_mut().zip(f.bytes()) {
-            *mem_byte = file_byte?;
+        Ok(())
+    }
+    /**
+     * The opcode executed by the last cycle
+     */
+    pub fn opcode(&self) -> u16 {
+        self.opcode
+    }
+    pub fn clock_speed(&self) -> u64 {
+        self.clock_speed
+    }
+    /**
+     * Set how many instructions are executed per second, from 1 Hz up to
+     * 10 MHz. This can be changed at any time without affecting the 60 Hz
+     * timers
+     */
+    pub fn set_clock_speed(&mut self, hz: u64) {
+        self.clock_speed = hz.clamp(1, MAX_CLOCK_SPEED_HZ);
+    }
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+    /**
+     * Give the next frame its share of cycles, then call `emulate_cycle`
+     * for as long as `frame_cycles_left` says so.
+     *
+     * With clock timing, when the clock speed isn't a multiple of the
+     * frame rate, the leftover fractions of an instruction are carried
+     * over so that none are lost, e.g. 1000 Hz runs frames of 16 and 17
+     * instructions. With VIP timing, an instruction that ran past the end
+     * of the last frame takes its extra cycles out of this one
+     */
+    pub fn start_frame(&mut self) {
+        let cycles = match self.timing {
+            Timing::Clock => {
+                self.cycle_remainder += self.clock_speed;
+                let cycles = self.cycle_remainder / TARGET_FPS;
+                self.cycle_remainder %= TARGET_FPS;
+                cycles
+            }
+            Timing::Vip => VIP_CYCLES_PER_FRAME,
+        };
+        // Cycles left over from a frame that ended early are dropped
+        self.frame_cycles = match self.timing {
+            Timing::Clock => cycles as i64,
+            Timing::Vip => self.frame_cycles.min(0) + cycles as i64,
+        };
+    }
+    /**
+     * Whether there's time left in this frame for another instruction
+     */
+    pub fn frame_cycles_left(&self) -> bool {
+        self.frame_cycles > 0
+    }
+    pub fn memory_size(&self) -> MemorySize {
+        // Memory is only ever allocated at one of the two sizes
+        MemorySize::from_bytes(self.memory.len()).unwrap_or(MemorySize::Extended)
+    }
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    pub fn rng_seed(&self) -> u64 {
+        self.rng_seed
+    }
+    /**
+     * Restart the random number generator from `seed`, so that CXNN gives
+     * the same numbers as any other run from the same seed
+     */
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_seed = seed;
+        self.random.seed(seed);
+    }
+    /**
+     * The built-in random number source in use, or `None` if one was
+     * given to `set_random_source`
+     */
+    pub fn random_mode(&self) -> Option<RandomMode> {
+        self.random_mode
+    }
+    /**
+     * Switch to one of the built-in random number sources, starting from
+     * the current seed
+     */
+    pub fn set_random_mode(&mut self, mode: RandomMode) {
+        self.set_random_source(mode.source());
+        self.random_mode = Some(mode);
+    }
+    /**
+     * Take CXNN's random numbers from `source`, starting from the current seed
+     */
+    pub fn set_random_source(&mut self, source: Box<dyn RandomSource>) {
+        self.random = source;
+        self.random_mode = None;
+        self.random.seed(self.rng_seed);
+    }
+    /**
+     * SHA-1 of the loaded ROM as a lowercase hex string, for identifying the game
+     */
+    pub fn rom_hash(&self) -> String {
+        self.rom_hash
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+    pub fn stack_pointer(&self) -> u8 {
+        self.stack_pointer
+    }
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+    /**
+     * The opcode that the next cycle will execute
+     */
+    pub fn next_opcode(&self) -> u16 {
+        self.word_at(self.program_counter as usize)
+            .unwrap_or(0x0000)
+    }
+    pub fn memory_accesses(&self) -> &[MemoryAccess] {
+        &self.memory_accesses
+    }
+    /**
+     * The big-endian word at `address`, if it lies inside memory
+     */
+    fn word_at(&self, address: usize) -> Option<u16> {
+        let bytes = self.memory.get(address..address + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+    /**
+     * Record a memory access made by the current opcode, failing
+     * if any of it falls outside of memory
+     */
+    fn log_memory_access(
+        &mut self,
+        kind: AccessKind,
+        address: usize,
+        len: usize,
+    ) -> Result<(), Error> {
+        if address + len > self.memory.len() {
+            return Err(Error::MemoryOutOfBounds {
+                address: address.max(self.memory.len()),
+                pc: self.program_counter,
+                opcode: self.opcode,
+            });
         }
+        self.memory_accesses
+            .push(MemoryAccess { kind, address, len });
+        Ok(())
+    }
+    /**
+     * Build an error for the instruction currently being executed
+     */
+    fn fault(&self, error: fn(u16, u16) -> Error) -> Error {
+        error(self.program_counter, self.opcode)
+    }
+    /**
+     * Video RAM, one byte per pixel in row-major order. Each byte is a
+     * bitmask of the XO-CHIP planes the pixel is lit on, so plain CHIP-8
+     * programs only ever produce 0 or 1
+     */
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.gfx
+    }
+    pub fn display_width(&self) -> usize {
+        match self.hires {
+            true => HIRES_VIDEO_WIDTH,
+            false => VIDEO_WIDTH,
+        }
+    }
+    pub fn display_height(&self) -> usize {
+        match self.hires {
+            true => HIRES_VIDEO_HEIGHT,
+            false => VIDEO_HEIGHT,
+        }
+    }
+    /**
+     * Whether the buzzer is sounding, which it does for as long as the
+     * sound timer is nonzero
+     */
+    pub fn is_sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+    /**
+     * The XO-CHIP audio pattern buffer and the rate in Hz at which its
+     * 128 bits are played back, if the program has loaded one
+     */
+    pub fn audio_pattern(&self) -> Option<([u8; 16], f32)> {
+        let rate = 4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0);
+        self.audio_pattern.map(|pattern| (pattern, rate))
+    }
+    /**
+     * Skip over the next instruction, taking into account that
+     * XO-CHIP's F000 NNNN is twice as long as every other opcode
+     */
+    fn skip_next_instruction(&mut self) {
+        let next = self.program_counter as usize + 2;
 
+        let skipped = match self.word_at(next) {
+            Some(0xF000) => 4,
+            _ => 2,
+        };
+        self.program_counter = self.program_counter.wrapping_add(skipped);
+    }
+    /**
+     * Move I on after Fx55/Fx65 has stored or loaded v0 to vX, as far
+     * as the load/store quirks say to
+     */
+    fn advance_index_register(&mut self, x: usize) {
+        let advance = match (self.quirks.load_store, self.quirks.load_store_by_x) {
+            (false, _) => 0,
+            (true, true) => x as u16,
+            (true, false) => x as u16 + 1,
+        };
+        self.index_register = self.index_register.wrapping_add(advance);
+    }
+    /**
+     * Switch between the 64x32 and SUPER-CHIP 128x64 display modes.
+     * The screen is cleared whenever the resolution changes
+     */
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.gfx = vec![0x00; self.display_width() * self.display_height()];
+        self.draw_flag = true;
+    }
+    pub fn set_keys(&mut self, keypad_state: &[bool]) {
+        for (key, pressed) in keypad_state.iter().take(16).enumerate() {
+            self.set_key(key, *pressed);
+        }
+    }
+    /**
+     * Press or release a single hex keypad key, 0x0 to 0xF
+     */
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if let Some(key_register) = self.keypad.get_mut(key) {
+            // Only a key changing counts as a press or a release, not one
+            // held down or left up
+            match (pressed, *key_register) {
+                (true, false) => self.key_presses |= 1 << key,
+                (false, true) => self.key_releases |= 1 << key,
+                _ => {}
+            }
+            *key_register = pressed;
+        }
+    }
+    /**
+     * Keys that have been pressed since the last vertical blank, with
+     * bit N set for key N
+     */
+    pub fn key_presses(&self) -> u16 {
+        self.key_presses
+    }
+    /**
+     * Keys that have been released since the last vertical blank, with
+     * bit N set for key N
+     */
+    pub fn key_releases(&self) -> u16 {
+        self.key_releases
+    }
+    /**
+     * Execute up to `cycles` instructions, stopping early if the program exits
+     */
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<(), Error> {
+        for _ in 0..cycles {
+            if self.exit_flag {
+                break;
+            }
+            self.emulate_cycle()?;
+        }
         Ok(())
     }
-    pub fn set_keys(&mut self, keypad_state: Vec<bool>) {
-        for (key_register, key_state) in self.keypad.iter_mut().zip(keypad_state.iter()) {
-            *key_register = *key_state;
+    /**
+     * Execute one 60 Hz frame's worth of instructions, then tick the timers.
+     *
+     * Unlike `emulate_cycle`, the draw flag is left set if any cycle in
+     * the frame raised it
+     */
+    pub fn run_frame(&mut self) -> Result<(), Error> {
+        self.run_frame_until(|_| false).map(|_| ())
+    }
+    /**
+     * Like `run_frame`, but `stop` is checked before every cycle and the
+     * frame ends early as soon as it returns true, without ticking the
+     * timers. Returns whether it did
+     */
+    pub fn run_frame_until(&mut self, mut stop: impl FnMut(&Chip8) -> bool) -> Result<bool, Error> {
+        let mut draw_flag = false;
+        let mut stopped = false;
+
+        self.start_frame();
+        while self.frame_cycles_left() {
+            if stop(self) {
+                stopped = true;
+                break;
+            }
+            if self.exit_flag {
+                break;
+            }
+            self.emulate_cycle()?;
+            draw_flag |= self.draw_flag;
+        }
+
+        self.draw_flag = draw_flag;
+
+        if !stopped {
+            self.vertical_blank();
+        }
+
+        Ok(stopped)
+    }
+    /**
+     * Signal the 60 Hz vertical blank at the end of a frame: the timers
+     * tick, a DXYN waiting on the display wait quirk can go ahead, and the
+     * key presses and releases start over for the next frame. Frontends
+     * that run their own frames call this instead of `tick_timers`
+     */
+    pub fn vertical_blank(&mut self) {
+        self.vblank = true;
+        self.tick_timers();
+
+        self.key_presses = 0;
+        self.key_releases = 0;
+        if let KeyWait::Press(seen) = &mut self.key_wait {
+            *seen = 0;
         }
     }
-    pub fn emulate_cycle(&mut self) -> Result<(), Error> {
+    /**
+     * Decrement the delay and sound timers. Call this at 60 Hz, once per
+     * frame, no matter how many instructions are executed in between
+     */
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+    }
+    /**
+     * Execute one instruction. Returns how long it took: one cycle with
+     * clock timing, or its VIP machine cycles with VIP timing
+     */
+    pub fn emulate_cycle(&mut self) -> Result<u64, Error> {
         let mut pc_should_increment = true;
         self.draw_flag = false;
-        self.sound_flag = false;
+        self.memory_accesses.clear();
 
         // Usize casted pointers for indexing system memory
         let pc = self.program_counter as usize;
         let sp = self.stack_pointer as usize;
-        let I = self.index_register as usize;
+        let i = self.index_register as usize;
 
         // Fetch Opcode
-        self.opcode = u16::from_be_bytes([self.memory[pc], self.memory[pc + 1]]);
+        self.opcode = self
+            .word_at(pc)
+            .ok_or(Error::PcOutOfBounds { pc: pc as u16 })?;
+
+        // Worked out before the instruction changes anything it depends on
+        let cycles = match self.timing {
+            Timing::Clock => 1,
+            Timing::Vip => self.vip_cycles(),
+        };
+        self.frame_cycles -= cycles as i64;
 
         // These variables are derived from the opcode in many cases;
         // so much so that it makes sense to extract them here instead of
@@ -120,15 +640,37 @@ impl Chip8 {
             // 0 series opcodes
             0x0000 => {
                 match self.opcode & 0x00FF {
+                    // 00CN: Scroll display down N pixels
+                    0xC0..=0xCF => draw::scroll_down(self, n as usize),
+                    // 00DN: Scroll display up N pixels
+                    0xD0..=0xDF => draw::scroll_up(self, n as usize),
                     // 00E0: Clear screen
-                    0xE0 => self.gfx.iter_mut().for_each(|byte| *byte = 0x00),
+                    0xE0 => draw::clear(self),
                     // 00EE: Return from subroutine
                     0xEE => {
+                        if self.stack_pointer == 0 {
+                            return Err(
+                                self.fault(|pc, opcode| Error::StackUnderflow { pc, opcode })
+                            );
+                        }
                         self.stack_pointer -= 1;
                         let sp = self.stack_pointer as usize;
                         self.program_counter = self.stack[sp];
                     }
-                    op => return Err(Error::UnknownOpcode(op)),
+                    // 00FB: Scroll display right 4 pixels
+                    0xFB => draw::scroll_right(self, 4),
+                    // 00FC: Scroll display left 4 pixels
+                    0xFC => draw::scroll_left(self, 4),
+                    // 00FD: Exit the interpreter
+                    0xFD => {
+                        self.exit_flag = true;
+                        pc_should_increment = false;
+                    }
+                    // 00FE: Switch to 64x32 low resolution mode
+                    0xFE => self.set_hires(false),
+                    // 00FF: Switch to 128x64 high resolution mode
+                    0xFF => self.set_hires(true),
+                    _ => return Err(self.fault(|pc, opcode| Error::UnknownOpcode { pc, opcode })),
                 }
             }
             // 1NNN: Jump to NNN
@@ -139,6 +681,9 @@ impl Chip8 {
             // 2NNN: Call NNN
             0x2000 => {
                 // Store pc in the stack and increment sp
+                if sp >= self.stack.len() {
+                    return Err(self.fault(|pc, opcode| Error::StackOverflow { pc, opcode }));
+                }
                 self.stack[sp] = self.program_counter;
                 self.stack_pointer += 1;
 
@@ -152,19 +697,45 @@ impl Chip8 {
                 // so we can skip the next instruction by manually incrementing it here
                 // such that it increments twice
                 if vx == nn {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             }
             // 4XNN: Skip next instruction if vX != NN
             0x4000 => {
                 if vx != nn {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             }
-            // 5XY0: Skip next instruction if vX == vY
+            // 5 series opcodes
             0x5000 => {
-                if vx == vy {
-                    self.program_counter += 2;
+                // vX to vY, in descending order if X > Y
+                let register_range: Vec<usize> = match x <= y {
+                    true => (x..=y).collect(),
+                    false => (y..=x).rev().collect(),
+                };
+
+                match self.opcode & 0x000F {
+                    // 5XY0: Skip next instruction if vX == vY
+                    0x00 => {
+                        if vx == vy {
+                            self.skip_next_instruction();
+                        }
+                    }
+                    // 5XY2: Store vX to vY in memory starting at I. I is not affected
+                    0x02 => {
+                        self.log_memory_access(AccessKind::Write, i, register_range.len())?;
+                        for (offset, register) in register_range.into_iter().enumerate() {
+                            self.memory[i + offset] = self.registers[register];
+                        }
+                    }
+                    // 5XY3: Fill vX to vY with mem values starting from I. I is not affected
+                    0x03 => {
+                        self.log_memory_access(AccessKind::Read, i, register_range.len())?;
+                        for (offset, register) in register_range.into_iter().enumerate() {
+                            self.registers[register] = self.memory[i + offset];
+                        }
+                    }
+                    _ => return Err(self.fault(|pc, opcode| Error::UnknownOpcode { pc, opcode })),
                 }
             }
             // 6XNN: set VX to NN
@@ -185,14 +756,23 @@ impl Chip8 {
                     // 8XY1: set vX to vx OR vY
                     0x01 => {
                         self.registers[x] = vx | vy;
+                        if self.quirks.vf_reset {
+                            self.registers[0x0F] = 0x00;
+                        }
                     }
                     // 8XY2: set vX to (vX & vY)
                     0x02 => {
                         self.registers[x] = vx & vy;
+                        if self.quirks.vf_reset {
+                            self.registers[0x0F] = 0x00;
+                        }
                     }
                     // 8XY3: set vX to vx XOR vY
                     0x03 => {
                         self.registers[x] = vx ^ vy;
+                        if self.quirks.vf_reset {
+                            self.registers[0x0F] = 0x00;
+                        }
                     }
                     // 8XY4: Adds VY to VX. V[0xF] is set to 1 when there's a carry, and to 0 when there isn't.
                     0x04 => {
@@ -224,12 +804,14 @@ impl Chip8 {
                             false => 0x00,
                         };
                     }
-                    // 8XY6: Store the least significant bit of VX in VF, then shift VX to the right by 1
+                    // 8XY6: Shift VY (or VX, see `Quirks::shift`) to the right by 1 and store it in VX.
+                    // VF is set to the bit that was shifted out
                     0x06 => {
-                        let least_significant_bit = vx & 0x01;
-                        self.registers[0x0F] = least_significant_bit;
+                        let source = if self.quirks.shift { vx } else { vy };
+                        let least_significant_bit = source & 0x01;
 
-                        self.registers[x] = vx >> 1;
+                        self.registers[x] = source >> 1;
+                        self.registers[0x0F] = least_significant_bit;
                     }
                     // BXY7: Set VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
                     0x07 => {
@@ -244,80 +826,134 @@ impl Chip8 {
                             false => 0x00,
                         };
                     }
-                    // 8XYE: Store the most significant bit of VX in VF, then shift VX to the left by 1
+                    // 8XYE: Shift VY (or VX, see `Quirks::shift`) to the left by 1 and store it in VX.
+                    // VF is set to the bit that was shifted out
                     0x0E => {
+                        let source = if self.quirks.shift { vx } else { vy };
                         // 0x80 is b10000000, so using AND and shifting right by 7
                         // gets us the most significant bit
-                        let most_signficant_bit = (vx & 0x80) >> 7;
-                        self.registers[0x0F] = most_signficant_bit;
+                        let most_signficant_bit = (source & 0x80) >> 7;
 
-                        self.registers[x] = vx << 1;
+                        self.registers[x] = source << 1;
+                        self.registers[0x0F] = most_signficant_bit;
                     }
-                    op => return Err(Error::UnknownOpcode(op)),
+                    _ => return Err(self.fault(|pc, opcode| Error::UnknownOpcode { pc, opcode })),
                 }
             }
             // 9XY0: Skip next instruction if vX != vY
             0x9000 => {
                 if vx != vy {
-                    self.program_counter += 2;
+                    self.skip_next_instruction();
                 }
             }
             // ANNN: set index_register to NNN
             0xA000 => {
                 self.index_register = nnn;
             }
-            // BNNN: Jump to NNN + v0
+            // BNNN: Jump to NNN + v0 (or XNN + vX, see `Quirks::jump`)
             0xB000 => {
-                let v0 = self.registers[0] as u16;
-                self.program_counter = nnn + v0;
+                let offset = match self.quirks.jump {
+                    true => vx as u16,
+                    false => self.registers[0] as u16,
+                };
+                self.program_counter = nnn + offset;
                 pc_should_increment = false;
             }
             // CXNN: set vX to a random u8 & NN (bitwise &)
             0xC000 => {
-                let random: u8 = rand::thread_rng().gen();
+                let random = self.random.next_byte();
                 self.registers[x] = random & nn;
             }
-            // DXYN: draw to the display
-            0xD000 => draw::dxyn(self, vx, vy, n, I),
+            // DXYN: draw to the display. With the display wait quirk, it
+            // waits for a vertical blank by running again until there is one
+            0xD000 => {
+                // A vertical blank from before the DXYN was reached doesn't count
+                if self.quirks.display_wait && !self.display_waiting {
+                    self.display_waiting = true;
+                    self.vblank = false;
+                }
+                match self.quirks.display_wait && !self.vblank {
+                    true => pc_should_increment = false,
+                    false => {
+                        self.display_waiting = false;
+                        draw::dxyn(self, vx, vy, n, i)?;
+                    }
+                }
+            }
             // E series opcodes
             0xE000 => {
-                let vx = vx as usize;
+                // Only the low nibble of vX picks a key, as on the COSMAC VIP
+                let vx = (vx & 0x0F) as usize;
                 match self.opcode & 0x00FF {
                     // EX9E: Skip next instruction if key in vX is pressed
                     0x9E => {
                         if self.keypad[vx] {
-                            self.program_counter += 2;
+                            self.skip_next_instruction();
                         }
                     }
                     // EXA1: Skip next instruction if key in vX is NOT pressed
                     0xA1 => {
                         if !self.keypad[vx] {
-                            self.program_counter += 2;
+                            self.skip_next_instruction();
                         }
                     }
-                    op => return Err(Error::UnknownOpcode(op)),
+                    _ => return Err(self.fault(|pc, opcode| Error::UnknownOpcode { pc, opcode })),
                 }
             }
             // F series opcodes
             0xF000 => {
                 match self.opcode & 0x00FF {
-                    // Fx15: Set vX to value of delay timer
+                    // F000 NNNN: Set index_register to the 16 bit address NNNN
+                    0x00 if x == 0 => {
+                        let next = pc + 2;
+                        let address = self.word_at(next).ok_or(Error::MemoryOutOfBounds {
+                            address: next,
+                            pc: self.program_counter,
+                            opcode: self.opcode,
+                        })?;
+                        self.index_register = address;
+                        self.program_counter = self.program_counter.wrapping_add(2);
+                    }
+                    // FN01: Select bitplanes N for drawing
+                    0x01 => {
+                        self.selected_planes = x as u8;
+                    }
+                    // F002: Load 16 bytes starting at I into the audio pattern buffer
+                    0x02 if x == 0 => {
+                        self.log_memory_access(AccessKind::Read, i, 16)?;
+                        let mut pattern = [0x00; 16];
+                        pattern.copy_from_slice(&self.memory[i..i + 16]);
+                        self.audio_pattern = Some(pattern);
+                    }
+                    // Fx07: Set vX to value of delay timer
                     0x07 => {
                         self.registers[x] = self.delay_timer;
                     }
-                    // Fx0A: wait for keypress, store in vX
+                    // Fx0A: wait for keypress, store in vX.
+                    // Keys already held down when the wait starts don't count
                     0x0A => {
-                        let mut any_key_pressed = false;
-
-                        for key in 0..0x0F {
-                            if self.keypad[key] {
-                                self.registers[x] = key as u8;
-                                any_key_pressed = true;
-                            }
-                        }
+                        let (key_wait, key) = match self.key_wait {
+                            KeyWait::Idle => (KeyWait::Press(self.key_presses), None),
+                            // The lowest numbered key wins if several are pressed at once
+                            KeyWait::Press(seen) => match self.key_presses & !seen {
+                                0 => (KeyWait::Press(seen), None),
+                                fresh if self.quirks.key_release => {
+                                    (KeyWait::Release(fresh.trailing_zeros() as u8), None)
+                                }
+                                fresh => (KeyWait::Idle, Some(fresh.trailing_zeros() as u8)),
+                            },
+                            // Released since being pressed, or pressed and
+                            // released again between two cycles
+                            KeyWait::Release(key) => match self.keypad[key as usize] {
+                                true => (KeyWait::Release(key), None),
+                                false => (KeyWait::Idle, Some(key)),
+                            },
+                        };
+                        self.key_wait = key_wait;
 
-                        if !any_key_pressed {
-                            pc_should_increment = false;
+                        match key {
+                            Some(key) => self.registers[x] = key,
+                            None => pc_should_increment = false,
                         }
                     }
                     // Fx15: Set delay timer to vX
@@ -330,68 +966,72 @@ impl Chip8 {
                     }
                     // Fx1E: Adds VX to I. VF is not affected
                     0x1E => {
-                        self.index_register += vx as u16;
+                        self.index_register = self.index_register.wrapping_add(vx as u16);
                     }
                     // Fx29: Set I to the location of the sprite for the character in vX
-                    // Fontset should already be loaded in memory at 0x50
+                    // Fontset should already be loaded in memory at 0x50. Only the
+                    // low nibble of vX picks a character, as on the COSMAC VIP
                     0x29 => {
-                        let vx = vx as u16;
+                        let vx = (vx & 0x0F) as u16;
                         let font_sprite_address = FONTSET_START_ADDRESS + (vx * 5);
                         self.index_register = font_sprite_address;
                     }
+                    // Fx30: Set I to the location of the large sprite for the character in vX
+                    0x30 => {
+                        let vx = (vx & 0x0F) as u16;
+                        let font_sprite_address = LARGE_FONTSET_START_ADDRESS + (vx * 10);
+                        self.index_register = font_sprite_address;
+                    }
                     // Fx33 (hard to explain, check wikipedia)
                     0x33 => {
                         let hundreds = (vx / 100) % 10;
                         let tens = (vx / 10) % 10;
                         let ones = vx % 10;
 
-                        self.memory[I] = hundreds;
-                        self.memory[I + 1] = tens;
-                        self.memory[I + 2] = ones;
+                        self.log_memory_access(AccessKind::Write, i, 3)?;
+                        self.memory[i] = hundreds;
+                        self.memory[i + 1] = tens;
+                        self.memory[i + 2] = ones;
                     }
                     // Fx55: Store v0 to vX (including vX) in memory starting at I
                     0x55 => {
+                        self.log_memory_access(AccessKind::Write, i, x + 1)?;
                         for offset in 0..=x {
-                            self.memory[I + offset] = self.registers[offset];
+                            self.memory[i + offset] = self.registers[offset];
                         }
+                        self.advance_index_register(x);
                     }
                     // Fx65: Fill v0 to vX (including vX) with mem values starting from I
                     0x65 => {
+                        self.log_memory_access(AccessKind::Read, i, x + 1)?;
                         for offset in 0..=x {
-                            self.registers[offset] = self.memory[I + offset];
+                            self.registers[offset] = self.memory[i + offset];
                         }
+                        self.advance_index_register(x);
+                    }
+                    // Fx3A: Set the audio pattern playback pitch to vX
+                    0x3A => {
+                        self.pitch = vx;
+                    }
+                    // Fx75: Store v0 to vX (including vX) in the RPL user flags
+                    0x75 => {
+                        self.rpl_flags[..=x].copy_from_slice(&self.registers[..=x]);
+                    }
+                    // Fx85: Fill v0 to vX (including vX) from the RPL user flags
+                    0x85 => {
+                        self.registers[..=x].copy_from_slice(&self.rpl_flags[..=x]);
                     }
-                    op => return Err(Error::UnknownOpcode(op)),
+                    _ => return Err(self.fault(|pc, opcode| Error::UnknownOpcode { pc, opcode })),
                 }
             }
-            op => return Err(Error::UnknownOpcode(op)),
+            _ => return Err(self.fault(|pc, opcode| Error::UnknownOpcode { pc, opcode })),
         }
 
         // Move pc 2 bytes to next opcode (unless current opcode has prevented it)
         if pc_should_increment {
-            self.program_counter += 2;
+            self.program_counter = self.program_counter.wrapping_add(2);
         }
 
-        // Because timers on chip8 only update 60 times/sec, but the clock speed in this
-        // emulator is configurable, we can determine exactly when the timers should
-        // decrement with a bit of math
-        self.timer_loop = (self.timer_loop + 1) % CYCLES_PER_TIMER_DECREMENT as u16;
-        let should_timer_update = self.timer_loop == 0;
-
-        // Update timers
-        if should_timer_update {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
-
-            if self.sound_timer > 0 {
-                if self.sound_timer == 1 {
-                    self.sound_flag = true;
-                }
-                self.sound_timer -= 1;
-            }
-        }
-
-        Ok(())
+        Ok(cycles)
     }
 }
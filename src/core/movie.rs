@@ -0,0 +1,201 @@
+use super::error::Error;
+use super::quirks::Quirks;
+use super::random::RandomMode;
+use super::state::{StateReader, StateWriter};
+use super::timing::Timing;
+use super::MemorySize;
+use crate::Chip8;
+
+// Movie header: magic bytes, format version, then the SHA-1 of the ROM
+const MAGIC: &[u8; 4] = b"C8MV";
+const VERSION: u16 = 5;
+
+/**
+ * The keypad on every frame of a game, recorded from the moment it was
+ * switched on. Along with the machine's quirks, memory size, clock speed,
+ * timing and random seed, this is enough to play the game back exactly as it was
+ */
+#[derive(Clone, Debug)]
+pub struct Movie {
+    rom_hash: [u8; 20],
+    quirks: Quirks,
+    memory_size: MemorySize,
+    clock_speed: u64,
+    timing: Timing,
+    // Movies made with a random number source from outside the emulator
+    // are played back with the standard one, and won't play back the same
+    random_mode: RandomMode,
+    rng_seed: u64,
+    // One bit per key, key 0 in the lowest bit
+    frames: Vec<u16>,
+}
+
+impl Movie {
+    /**
+     * Start recording `chip8`, which should have just been reset and had
+     * its ROM loaded
+     */
+    pub fn new(chip8: &Chip8) -> Self {
+        Movie {
+            rom_hash: chip8.rom_hash,
+            quirks: chip8.quirks,
+            memory_size: chip8.memory_size(),
+            clock_speed: chip8.clock_speed,
+            timing: chip8.timing,
+            random_mode: chip8.random_mode.unwrap_or_default(),
+            rng_seed: chip8.rng_seed,
+            frames: Vec::new(),
+        }
+    }
+
+    /**
+     * A machine in the state the movie was recorded from, with `rom` loaded
+     */
+    pub fn start(&self, rom: &[u8]) -> Result<Chip8, Error> {
+        let mut chip8 = Chip8::new(self.quirks, self.memory_size);
+        chip8.set_clock_speed(self.clock_speed);
+        chip8.set_timing(self.timing);
+        chip8.set_random_mode(self.random_mode);
+        chip8.set_rng_seed(self.rng_seed);
+        chip8.initialize();
+        chip8.load_rom(rom)?;
+
+        if chip8.rom_hash != self.rom_hash {
+            return Err(Error::MovieRomMismatch);
+        }
+
+        Ok(chip8)
+    }
+
+    /**
+     * Number of frames recorded
+     */
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /**
+     * Record the keys held for the frame about to be run
+     */
+    pub fn record_frame(&mut self, chip8: &Chip8) {
+        let keys = chip8
+            .keypad
+            .iter()
+            .enumerate()
+            .fold(0, |keys, (key, held)| keys | (*held as u16) << key);
+        self.frames.push(keys);
+    }
+
+    /**
+     * Hold down the keys recorded for `frame`. Returns false once the
+     * movie has run out of frames
+     */
+    pub fn play_frame(&self, frame: usize, chip8: &mut Chip8) -> bool {
+        let keys = match self.frames.get(frame) {
+            Some(keys) => *keys,
+            None => return false,
+        };
+        for key in 0..16 {
+            chip8.set_key(key, keys & (1 << key) != 0);
+        }
+        true
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = StateWriter::default();
+
+        w.bytes(MAGIC);
+        w.u16(VERSION);
+        w.bytes(&self.rom_hash);
+
+        let Quirks {
+            shift,
+            load_store,
+            load_store_by_x,
+            jump,
+            vf_reset,
+            clipping,
+            display_wait,
+            key_release,
+        } = self.quirks;
+        for quirk in [
+            shift,
+            load_store,
+            load_store_by_x,
+            jump,
+            vf_reset,
+            clipping,
+            display_wait,
+            key_release,
+        ] {
+            w.bool(quirk);
+        }
+        w.u32(self.memory_size.bytes() as u32);
+        w.u64(self.clock_speed);
+        w.bool(self.timing == Timing::Vip);
+        w.bool(self.random_mode == RandomMode::Vip);
+        w.u64(self.rng_seed);
+
+        w.u32(self.frames.len() as u32);
+        for keys in self.frames.iter() {
+            w.u16(*keys);
+        }
+
+        w.buffer
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Movie::read(&mut StateReader { buffer: bytes }).map_err(|_| Error::InvalidMovie)
+    }
+
+    fn read(r: &mut StateReader) -> Result<Self, Error> {
+        if r.bytes(MAGIC.len())? != MAGIC || r.u16()? != VERSION {
+            return Err(Error::InvalidMovie);
+        }
+
+        let mut rom_hash = [0x00; 20];
+        rom_hash.copy_from_slice(r.bytes(20)?);
+
+        let quirks = Quirks {
+            shift: r.bool()?,
+            load_store: r.bool()?,
+            load_store_by_x: r.bool()?,
+            jump: r.bool()?,
+            vf_reset: r.bool()?,
+            clipping: r.bool()?,
+            display_wait: r.bool()?,
+            key_release: r.bool()?,
+        };
+        let memory_size = MemorySize::from_bytes(r.u32()? as usize).ok_or(Error::InvalidMovie)?;
+        let clock_speed = r.u64()?;
+        let timing = match r.bool()? {
+            true => Timing::Vip,
+            false => Timing::Clock,
+        };
+        let random_mode = match r.bool()? {
+            true => RandomMode::Vip,
+            false => RandomMode::Standard,
+        };
+        let rng_seed = r.u64()?;
+
+        let frame_count = r.u32()?;
+        let frames = (0..frame_count)
+            .map(|_| r.u16())
+            .collect::<Result<Vec<u16>, Error>>()?;
+
+        Ok(Movie {
+            rom_hash,
+            quirks,
+            memory_size,
+            clock_speed,
+            timing,
+            random_mode,
+            rng_seed,
+            frames,
+        })
+    }
+}
@@ -0,0 +1,130 @@
+/**
+ * Behaviours that differ between CHIP-8 interpreters. Each flag turns on
+ * the behaviour described next to it, and the opcode does the other
+ * thing when it's off
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift vX in place, ignoring vY
+    pub shift: bool,
+    // Fx55/Fx65 move I on past the last register stored/loaded, rather
+    // than leaving it where it was
+    pub load_store: bool,
+    // Along with `load_store`, I is left on the last register instead of past it
+    pub load_store_by_x: bool,
+    // BNNN is read as BXNN and jumps to XNN + vX
+    pub jump: bool,
+    // 8XY1/8XY2/8XY3 reset vF to 0
+    pub vf_reset: bool,
+    // DXYN clips sprites at the screen edge instead of wrapping them around
+    pub clipping: bool,
+    // DXYN waits for the next vertical blank before drawing
+    pub display_wait: bool,
+    // Fx0A waits for the key to be released again, not just pressed
+    pub key_release: bool,
+}
+
+/**
+ * Named quirk profiles for the common CHIP-8 platforms,
+ * selectable with the `quirks` key in `settings.yaml`
+ */
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum QuirksPreset {
+    #[serde(rename = "vip")]
+    Vip,
+    #[serde(rename = "chip-48")]
+    #[default]
+    Chip48,
+    #[serde(rename = "super-chip")]
+    SuperChip,
+    #[serde(rename = "xo-chip")]
+    XoChip,
+    #[serde(rename = "modern")]
+    Modern,
+}
+
+impl QuirksPreset {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            // Original COSMAC VIP interpreter
+            QuirksPreset::Vip => Quirks {
+                shift: false,
+                load_store: true,
+                load_store_by_x: false,
+                jump: false,
+                vf_reset: true,
+                clipping: true,
+                display_wait: true,
+                key_release: true,
+            },
+            // HP48 calculators, where Fx55/Fx65 move I on by one less than the VIP
+            QuirksPreset::Chip48 => Quirks {
+                shift: true,
+                load_store: true,
+                load_store_by_x: true,
+                jump: true,
+                vf_reset: false,
+                clipping: true,
+                display_wait: false,
+                key_release: false,
+            },
+            // SUPER-CHIP 1.1, which unlike CHIP-48 leaves I alone after Fx55/Fx65
+            QuirksPreset::SuperChip => Quirks {
+                shift: true,
+                load_store: false,
+                load_store_by_x: false,
+                jump: true,
+                vf_reset: false,
+                clipping: true,
+                display_wait: false,
+                key_release: false,
+            },
+            // Octo's XO-CHIP
+            QuirksPreset::XoChip => Quirks {
+                shift: false,
+                load_store: true,
+                load_store_by_x: false,
+                jump: false,
+                vf_reset: false,
+                clipping: false,
+                display_wait: false,
+                key_release: true,
+            },
+            // What most present-day CHIP-8 emulators and games expect
+            QuirksPreset::Modern => Quirks {
+                shift: false,
+                load_store: true,
+                load_store_by_x: false,
+                jump: false,
+                vf_reset: false,
+                clipping: true,
+                display_wait: false,
+                key_release: true,
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for QuirksPreset {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "vip" => Ok(QuirksPreset::Vip),
+            "chip-48" => Ok(QuirksPreset::Chip48),
+            "super-chip" => Ok(QuirksPreset::SuperChip),
+            "xo-chip" => Ok(QuirksPreset::XoChip),
+            "modern" => Ok(QuirksPreset::Modern),
+            _ => Err(format!(
+                "Unknown quirks preset `{}`, expected vip, chip-48, super-chip, xo-chip or modern",
+                name
+            )),
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        QuirksPreset::default().quirks()
+    }
+}
@@ -0,0 +1,157 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+/**
+ * Where CXNN gets its random numbers from. A source can be given to
+ * `Chip8::set_random_source` to control exactly what a program sees
+ */
+pub trait RandomSource: Send {
+    /**
+     * Start the sequence of numbers over from `seed`
+     */
+    fn seed(&mut self, seed: u64);
+    /**
+     * The next random byte
+     */
+    fn next_byte(&mut self) -> u8;
+    /**
+     * Everything needed to carry on the sequence from where it is now,
+     * for save states. Sources that can't be saved give nothing
+     */
+    fn state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    /**
+     * Carry on the sequence from a `state`. Returns false if it isn't
+     * one that this source could have given
+     */
+    fn set_state(&mut self, state: &[u8]) -> bool {
+        state.is_empty()
+    }
+    fn box_clone(&self) -> Box<dyn RandomSource>;
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+/**
+ * The built-in random number sources, selectable with the `random`
+ * key in `settings.yaml`
+ */
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RandomMode {
+    // Good quality random numbers
+    #[default]
+    Standard,
+    // The COSMAC VIP interpreter's own method
+    Vip,
+}
+
+impl RandomMode {
+    pub fn source(self) -> Box<dyn RandomSource> {
+        match self {
+            RandomMode::Standard => Box::new(StdRandom(ChaCha12Rng::seed_from_u64(0))),
+            RandomMode::Vip => Box::new(VipRandom { seed: 0 }),
+        }
+    }
+}
+
+/**
+ * ChaCha12, the same generator as rand's `StdRng`, but used directly so
+ * that how far through its sequence it is can be saved
+ */
+#[derive(Clone)]
+pub struct StdRandom(ChaCha12Rng);
+
+impl RandomSource for StdRandom {
+    fn seed(&mut self, seed: u64) {
+        self.0 = ChaCha12Rng::seed_from_u64(seed);
+    }
+    fn next_byte(&mut self) -> u8 {
+        self.0.gen()
+    }
+    // The 32-byte key, then the position in the stream
+    fn state(&self) -> Vec<u8> {
+        let mut state = self.0.get_seed().to_vec();
+        state.extend_from_slice(&self.0.get_word_pos().to_be_bytes());
+        state
+    }
+    fn set_state(&mut self, state: &[u8]) -> bool {
+        if state.len() != 48 {
+            return false;
+        }
+        let mut seed = [0x00; 32];
+        seed.copy_from_slice(&state[..32]);
+        let mut word_pos = [0x00; 16];
+        word_pos.copy_from_slice(&state[32..]);
+
+        self.0 = ChaCha12Rng::from_seed(seed);
+        self.0.set_word_pos(u128::from_be_bytes(word_pos));
+        true
+    }
+    fn box_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+/**
+ * Stands in for page 0x100-0x1FF of the COSMAC VIP's memory, which held
+ * the CHIP-8 interpreter's own code. Those bytes aren't bundled, so this
+ * is a fixed shuffle of every byte value instead, which like the code
+ * varies from one byte to the next. Replacing it with the real page
+ * would give the VIP's own sequence
+ */
+const VIP_PAGE: [u8; 256] = shuffled_page();
+
+const fn shuffled_page() -> [u8; 256] {
+    let mut page = [0x00; 256];
+    let mut i = 0;
+    while i < page.len() {
+        let byte = (i as u8).wrapping_mul(0x9D).wrapping_add(0x5B);
+        page[i] = (byte ^ (byte >> 3)).wrapping_mul(0x35);
+        i += 1;
+    }
+    page
+}
+
+/**
+ * The original COSMAC VIP interpreter's CXNN. A 16-bit seed counts up by
+ * one for every number, and the byte that its low half picks out of the
+ * interpreter's page 0x100-0x1FF is added to its high half, which is the
+ * random number
+ */
+#[derive(Clone)]
+pub struct VipRandom {
+    seed: u16,
+}
+
+impl RandomSource for VipRandom {
+    fn seed(&mut self, seed: u64) {
+        self.seed = seed as u16;
+    }
+    fn next_byte(&mut self) -> u8 {
+        let [high, low] = self.seed.wrapping_add(1).to_be_bytes();
+        let high = high.wrapping_add(VIP_PAGE[low as usize]);
+        self.seed = u16::from_be_bytes([high, low]);
+        high
+    }
+    fn state(&self) -> Vec<u8> {
+        self.seed.to_be_bytes().to_vec()
+    }
+    fn set_state(&mut self, state: &[u8]) -> bool {
+        match state {
+            [high, low] => {
+                self.seed = u16::from_be_bytes([*high, *low]);
+                true
+            }
+            _ => false,
+        }
+    }
+    fn box_clone(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
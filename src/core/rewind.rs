@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+
+/**
+ * Ring buffer of save states used to play the game backwards.
+ *
+ * Only the most recent state is kept in full. Every older frame is stored
+ * as the XOR of itself with the frame after it, with runs of unchanged
+ * (zero) bytes squashed. Since most of memory is static between frames,
+ * these deltas are tiny compared to a full state.
+ */
+pub struct RewindBuffer {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    history: VecDeque<Entry>,
+}
+
+enum Entry {
+    // XOR delta to get from the next frame back to this one
+    Delta(Vec<u8>),
+    // Full copy, for when the state size changed between frames
+    Full(Vec<u8>),
+}
+
+impl RewindBuffer {
+    /**
+     * Create a buffer holding up to `capacity` frames
+     */
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            latest: None,
+            history: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /**
+     * Record the state of a new frame, dropping the oldest frame if the buffer is full
+     */
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if let Some(previous) = self.latest.take() {
+            let entry = match previous.len() == state.len() {
+                true => Entry::Delta(compress(&xor(&previous, &state))),
+                false => Entry::Full(previous),
+            };
+
+            self.history.push_back(entry);
+            if self.history.len() >= self.capacity {
+                self.history.pop_front();
+            }
+        }
+
+        self.latest = Some(state);
+    }
+
+    /**
+     * Step one frame back in time, returning the state of that frame
+     */
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let latest = self.latest.take()?;
+
+        let previous = match self.history.pop_back() {
+            Some(Entry::Delta(delta)) => xor(&latest, &decompress(&delta, latest.len())),
+            Some(Entry::Full(state)) => state,
+            // This is the oldest frame there is, so stay on it
+            None => latest,
+        };
+
+        self.latest = Some(previous.clone());
+        Some(previous)
+    }
+
+    /**
+     * Forget every frame, for when the game starts over
+     */
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.history.clear();
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b.iter()).map(|(a, b)| a ^ b).collect()
+}
+
+/**
+ * Encode a delta as a list of chunks, each made of a little endian u32
+ * count of zero bytes to skip, a u32 count of literal bytes, then the literals
+ */
+fn compress(delta: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    let mut pos = 0;
+
+    while pos < delta.len() {
+        let zeros = delta[pos..].iter().take_while(|byte| **byte == 0).count();
+        pos += zeros;
+
+        let literals = delta[pos..].iter().take_while(|byte| **byte != 0).count();
+
+        compressed.extend_from_slice(&(zeros as u32).to_le_bytes());
+        compressed.extend_from_slice(&(literals as u32).to_le_bytes());
+        compressed.extend_from_slice(&delta[pos..pos + literals]);
+        pos += literals;
+    }
+
+    compressed
+}
+
+fn decompress(compressed: &[u8], len: usize) -> Vec<u8> {
+    let mut delta = Vec::with_capacity(len);
+    let mut pos = 0;
+
+    let read_u32 = |pos: usize| {
+        let mut bytes = [0x00; 4];
+        bytes.copy_from_slice(&compressed[pos..pos + 4]);
+        u32::from_le_bytes(bytes) as usize
+    };
+
+    while pos < compressed.len() {
+        let zeros = read_u32(pos);
+        let literals = read_u32(pos + 4);
+        pos += 8;
+
+        delta.resize(delta.len() + zeros, 0x00);
+        delta.extend_from_slice(&compressed[pos..pos + literals]);
+        pos += literals;
+    }
+
+    delta.resize(len, 0x00);
+    delta
+}
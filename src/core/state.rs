@@ -0,0 +1,230 @@
+use super::error::Error;
+use super::{KeyWait, MemorySize, Timing};
+use crate::Chip8;
+
+// Save state header: magic bytes, format version, then the SHA-1 of the ROM
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u16 = 8;
+
+impl Chip8 {
+    /**
+     * Serialize the full machine state into a versioned binary blob.
+     * The blob can only be restored into a machine running the same ROM
+     */
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::default();
+
+        w.bytes(MAGIC);
+        w.u16(VERSION);
+        w.bytes(&self.rom_hash);
+
+        w.u16(self.opcode);
+        w.u32(self.memory.len() as u32);
+        w.bytes(&self.memory);
+        w.bytes(&self.registers);
+        w.u16(self.index_register);
+        w.u16(self.program_counter);
+
+        w.bool(self.hires);
+        w.u8(self.selected_planes);
+        w.bytes(&self.gfx);
+
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+
+        for address in self.stack.iter() {
+            w.u16(*address);
+        }
+        w.u8(self.stack_pointer);
+
+        for key in self.keypad.iter() {
+            w.bool(*key);
+        }
+        w.u16(self.key_presses);
+        w.u16(self.key_releases);
+        let (key_wait, seen) = match self.key_wait {
+            KeyWait::Idle => (0x00, 0),
+            KeyWait::Press(seen) => (0x01, seen),
+            KeyWait::Release(key) => (0x10 | key, 0),
+        };
+        w.u8(key_wait);
+        w.u16(seen);
+
+        w.bytes(&self.rpl_flags);
+        w.bool(self.audio_pattern.is_some());
+        w.bytes(&self.audio_pattern.unwrap_or_default());
+        w.u8(self.pitch);
+
+        w.bool(self.exit_flag);
+        w.bool(self.vblank);
+        w.bool(self.display_waiting);
+
+        // Where the machine is up to within the current frame
+        w.bool(self.timing == Timing::Vip);
+        w.u64(self.cycle_remainder);
+        w.u64(self.frame_cycles as u64);
+
+        // How far CXNN is through its random numbers
+        w.u64(self.rng_seed);
+        let random = self.random.state();
+        w.u32(random.len() as u32);
+        w.bytes(&random);
+
+        w.buffer
+    }
+
+    /**
+     * Restore machine state from a blob produced by `save_state`.
+     * The machine is left untouched if the blob is invalid
+     */
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Error> {
+        let mut r = StateReader { buffer: state };
+
+        if r.bytes(MAGIC.len())? != MAGIC || r.u16()? != VERSION {
+            return Err(Error::InvalidSaveState);
+        }
+        if r.bytes(self.rom_hash.len())? != self.rom_hash {
+            return Err(Error::SaveStateRomMismatch);
+        }
+
+        // Read everything into a copy first, so that a truncated
+        // blob can't leave the machine half restored
+        let mut chip8 = self.clone();
+
+        chip8.opcode = r.u16()?;
+        let memory_size =
+            MemorySize::from_bytes(r.u32()? as usize).ok_or(Error::InvalidSaveState)?;
+        chip8.memory = r.bytes(memory_size.bytes())?.to_vec();
+        chip8.registers.copy_from_slice(r.bytes(16)?);
+        chip8.index_register = r.u16()?;
+        chip8.program_counter = r.u16()?;
+
+        chip8.hires = r.bool()?;
+        chip8.selected_planes = r.u8()?;
+        if chip8.selected_planes > 0b11 {
+            return Err(Error::InvalidSaveState);
+        }
+        let display_size = chip8.display_width() * chip8.display_height();
+        chip8.gfx = r.bytes(display_size)?.to_vec();
+
+        chip8.delay_timer = r.u8()?;
+        chip8.sound_timer = r.u8()?;
+
+        for address in chip8.stack.iter_mut() {
+            *address = r.u16()?;
+        }
+        chip8.stack_pointer = r.u8()?;
+        // The stack is indexed by the stack pointer without further checks
+        if chip8.stack_pointer as usize > chip8.stack.len() {
+            return Err(Error::InvalidSaveState);
+        }
+
+        for key in chip8.keypad.iter_mut() {
+            *key = r.bool()?;
+        }
+        chip8.key_presses = r.u16()?;
+        chip8.key_releases = r.u16()?;
+        let (key_wait, seen) = (r.u8()?, r.u16()?);
+        chip8.key_wait = match key_wait {
+            0x00 => KeyWait::Idle,
+            0x01 => KeyWait::Press(seen),
+            wait if wait & 0xF0 == 0x10 => KeyWait::Release(wait & 0x0F),
+            _ => return Err(Error::InvalidSaveState),
+        };
+
+        chip8.rpl_flags.copy_from_slice(r.bytes(16)?);
+        let has_audio_pattern = r.bool()?;
+        let mut audio_pattern = [0x00; 16];
+        audio_pattern.copy_from_slice(r.bytes(16)?);
+        chip8.audio_pattern = if has_audio_pattern {
+            Some(audio_pattern)
+        } else {
+            None
+        };
+        chip8.pitch = r.u8()?;
+
+        chip8.exit_flag = r.bool()?;
+        chip8.vblank = r.bool()?;
+        chip8.display_waiting = r.bool()?;
+
+        chip8.timing = match r.bool()? {
+            true => Timing::Vip,
+            false => Timing::Clock,
+        };
+        chip8.cycle_remainder = r.u64()?;
+        chip8.frame_cycles = r.u64()? as i64;
+
+        chip8.rng_seed = r.u64()?;
+        let random_len = r.u32()? as usize;
+        if !chip8.random.set_state(r.bytes(random_len)?) {
+            return Err(Error::InvalidSaveState);
+        }
+
+        // Whatever was on screen before the state was loaded is stale
+        chip8.draw_flag = true;
+
+        *self = chip8;
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(super) struct StateWriter {
+    pub(super) buffer: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(super) fn bytes(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+    pub(super) fn u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+    pub(super) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+    pub(super) fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+    pub(super) fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+    pub(super) fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+}
+
+pub(super) struct StateReader<'a> {
+    pub(super) buffer: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub(super) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.buffer.len() {
+            return Err(Error::InvalidSaveState);
+        }
+        let (bytes, rest) = self.buffer.split_at(len);
+        self.buffer = rest;
+        Ok(bytes)
+    }
+    pub(super) fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+    pub(super) fn bool(&mut self) -> Result<bool, Error> {
+        Ok(self.u8()? != 0)
+    }
+    pub(super) fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+    pub(super) fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    pub(super) fn u64(&mut self) -> Result<u64, Error> {
+        let mut bytes = [0x00; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
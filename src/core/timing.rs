@@ -0,0 +1,99 @@
+use crate::Chip8;
+
+// Machine cycles the COSMAC VIP's 1.76 MHz CDP1802 runs per 60 Hz frame,
+// at 8 clock cycles each
+pub const VIP_CYCLES_PER_FRAME: u64 = 3668;
+
+// Machine cycles the VIP interpreter spends fetching and decoding each instruction
+const FETCH_CYCLES: u64 = 40;
+
+/**
+ * How instructions are paced, selectable with the `timing` key in
+ * `settings.yaml`
+ */
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Timing {
+    // Every instruction takes the same time, set by the clock speed
+    #[default]
+    Clock,
+    // Instructions take as long as they did on the COSMAC VIP, and the
+    // clock speed is ignored
+    Vip,
+}
+
+impl Chip8 {
+    /**
+     * Roughly how many machine cycles the VIP interpreter takes to run
+     * the instruction about to be executed. Most instructions take a fixed
+     * time, but skips cost more when taken, sprites cost more per row when
+     * they aren't lined up with a byte, and Fx33 and the register loads
+     * and stores loop once per digit or register
+     */
+    pub(super) fn vip_cycles(&self) -> u64 {
+        let opcode = self.opcode;
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as u64;
+        let nn = (opcode & 0x00FF) as u8;
+        let vx = self.registers[x];
+        let vy = self.registers[y];
+
+        let skip = |taken: bool| match taken {
+            true => 14,
+            false => 10,
+        };
+        let key_held = self.keypad[(vx & 0x0F) as usize];
+
+        let cycles = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                // The VIP clears the screen a byte at a time
+                0x00E0 => 3078,
+                _ => 10,
+            },
+            0x1000 => 12,
+            0x2000 => 26,
+            0x3000 => skip(vx == nn),
+            0x4000 => skip(vx != nn),
+            0x5000 => skip(vx == vy) + 4,
+            0x6000 => 6,
+            0x7000 => 10,
+            0x8000 => match n {
+                0x0 => 12,
+                _ => 44,
+            },
+            0x9000 => skip(vx != vy) + 4,
+            0xA000 => 12,
+            0xB000 => 22,
+            0xC000 => 36,
+            0xD000 => match self.quirks.display_wait && !(self.display_waiting && self.vblank) {
+                // Only checking whether the vertical blank has come yet
+                true => 0,
+                false => {
+                    let row_cycles = match vx % 8 {
+                        0 => 20,
+                        _ => 34,
+                    };
+                    22 + n * row_cycles
+                }
+            },
+            0xE000 => match nn {
+                0x9E => skip(key_held) + 4,
+                0xA1 => skip(!key_held) + 4,
+                _ => 10,
+            },
+            _ => match nn {
+                0x0A => 18,
+                0x1E | 0x29 => 16,
+                0x33 => {
+                    let digits = (vx / 100 + vx / 10 % 10 + vx % 10) as u64;
+                    80 + 16 * digits
+                }
+                0x55 | 0x65 => 14 + 14 * (x as u64 + 1),
+                _ => 10,
+            },
+        };
+
+        FETCH_CYCLES + cycles
+    }
+}
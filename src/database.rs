@@ -0,0 +1,225 @@
+use crate::theme::{Colour, PaletteConfig};
+use crab_chip::{Quirks, QuirksPreset, TARGET_FPS};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/**
+ * Games known to the emulator, in the format of the community CHIP-8
+ * database's programs.json (https://github.com/chip-8/chip-8-database).
+ * It ships empty, and replacing it with that file before building
+ * recognises every game listed there
+ */
+const BUNDLED_PROGRAMS: &str = include_str!("../assets/database/programs.json");
+
+// The player's own additions and corrections, read from next to the settings file
+const USER_DATABASE_FILE_NAME: &str = "programs.json";
+
+/**
+ * A game, which may have several releases with different ROMs
+ */
+#[derive(serde::Deserialize, Clone, Debug)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    // Keyed by the SHA-1 of the ROM
+    #[serde(default)]
+    roms: HashMap<String, Rom>,
+}
+
+/**
+ * How a single ROM should be run
+ */
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct Rom {
+    // Platforms the ROM runs on, most suitable first
+    platforms: Vec<String>,
+    // Quirks that differ from the platform's usual ones, by platform
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+    // Instructions per frame
+    tickrate: Option<u64>,
+    // Which keypad keys the game uses for directions and buttons
+    keys: HashMap<String, u8>,
+    colors: Option<Colours>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    jump: Option<bool>,
+    logic: Option<bool>,
+    wrap: Option<bool>,
+    vblank: Option<bool>,
+}
+
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct Colours {
+    // Background, plane 1, plane 2 and both planes, as `#RRGGBB`
+    pixels: Vec<Colour>,
+}
+
+pub struct Database {
+    // The user's programs come first, so that they're found first
+    programs: Vec<Program>,
+}
+
+/**
+ * What the database knows about the loaded ROM
+ */
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    // The first of the ROM's platforms that there are quirks presets for
+    platform: Option<(String, QuirksPreset)>,
+    rom: Rom,
+}
+
+impl Database {
+    /**
+     * The bundled database, with the user's database file in `directory`
+     * (if there is one) taking precedence over it
+     */
+    pub fn load(directory: Option<&Path>) -> Result<Self, String> {
+        let mut programs = Vec::new();
+
+        if let Some(path) = directory.map(|dir| dir.join(USER_DATABASE_FILE_NAME)) {
+            if path.is_file() {
+                let json =
+                    fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let user: Vec<Program> = serde_json::from_str(&json)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                programs.extend(user);
+            }
+        }
+
+        let bundled: Vec<Program> = serde_json::from_str(BUNDLED_PROGRAMS)
+            .map_err(|e| format!("Bundled ROM database: {}", e))?;
+        programs.extend(bundled);
+
+        Ok(Database { programs })
+    }
+
+    /**
+     * Look up a ROM by the lowercase hex SHA-1 of its contents
+     */
+    pub fn find(&self, rom_hash: &str) -> Option<RomInfo> {
+        self.programs.iter().find_map(|program| {
+            let rom = program
+                .roms
+                .iter()
+                .find(|(hash, _)| hash.eq_ignore_ascii_case(rom_hash))
+                .map(|(_, rom)| rom)?;
+
+            let platform = rom.platforms.iter().find_map(|platform| {
+                platform_preset(platform).map(|preset| (platform.clone(), preset))
+            });
+
+            Some(RomInfo {
+                title: program.title.clone(),
+                authors: program.authors.clone(),
+                platform,
+                rom: rom.clone(),
+            })
+        })
+    }
+}
+
+impl RomInfo {
+    pub fn quirks_preset(&self) -> Option<QuirksPreset> {
+        self.platform.as_ref().map(|(_, preset)| *preset)
+    }
+
+    /**
+     * XO-CHIP games expect its 64 KiB of memory
+     */
+    pub fn extended_memory(&self) -> bool {
+        self.quirks_preset() == Some(QuirksPreset::XoChip)
+    }
+
+    pub fn clock_hz(&self) -> Option<u64> {
+        self.rom.tickrate.map(|tickrate| tickrate * TARGET_FPS)
+    }
+
+    /**
+     * The ROM's own colours, when it lists any
+     */
+    pub fn palette(&self) -> Option<PaletteConfig> {
+        self.rom
+            .colors
+            .as_ref()
+            .filter(|colours| !colours.pixels.is_empty())
+            .map(|colours| PaletteConfig::from_colours(&colours.pixels))
+    }
+
+    /**
+     * Keypad keys used for `up`, `down`, `left`, `right`, `a` and `b`
+     */
+    pub fn key_hints(&self) -> &HashMap<String, u8> {
+        &self.rom.keys
+    }
+
+    /**
+     * Apply any quirks that this ROM needs on its platform, and
+     * which the platform's preset doesn't already have
+     */
+    pub fn adjust_quirks(&self, quirks: &mut Quirks) {
+        let overrides = match &self.platform {
+            Some((platform, _)) => match self.rom.quirky_platforms.get(platform) {
+                Some(overrides) => *overrides,
+                None => return,
+            },
+            None => return,
+        };
+
+        // The database names quirks for the behaviour they turn on, some of
+        // which are the opposite of ours
+        let settings = [
+            (&mut quirks.shift, overrides.shift),
+            (
+                &mut quirks.load_store,
+                overrides.memory_leave_i_unchanged.map(|on| !on),
+            ),
+            (&mut quirks.load_store_by_x, overrides.memory_increment_by_x),
+            (&mut quirks.jump, overrides.jump),
+            (&mut quirks.vf_reset, overrides.logic),
+            (&mut quirks.clipping, overrides.wrap.map(|on| !on)),
+            (&mut quirks.display_wait, overrides.vblank),
+        ];
+        for (quirk, setting) in settings {
+            if let Some(on) = setting {
+                *quirk = on;
+            }
+        }
+    }
+
+    /**
+     * The title and authors, for the window title
+     */
+    pub fn description(&self) -> String {
+        match self.authors.is_empty() {
+            true => self.title.clone(),
+            false => format!("{} by {}", self.title, self.authors.join(", ")),
+        }
+    }
+}
+
+/**
+ * The quirks preset for one of the database's platform IDs. Platforms
+ * that the emulator can't run, or has no preset for, are `None`
+ */
+fn platform_preset(platform: &str) -> Option<QuirksPreset> {
+    match platform {
+        "originalChip8" | "hybridVIP" => Some(QuirksPreset::Vip),
+        "chip48" => Some(QuirksPreset::Chip48),
+        "superchip1" | "superchip" => Some(QuirksPreset::SuperChip),
+        "xochip" => Some(QuirksPreset::XoChip),
+        "modernChip8" => Some(QuirksPreset::Modern),
+        _ => None,
+    }
+}
@@ -0,0 +1,154 @@
+use crate::core::{Instruction, Syntax, PC_START_ADDRESS};
+use std::collections::{BTreeMap, BTreeSet};
+
+/**
+ * Disassemble a ROM into an assembly listing.
+ *
+ * Code is found by following every path of execution from the start of
+ * the program, so anything that is never reached (typically sprite data)
+ * is listed as raw bytes instead. Each line is annotated with its address
+ * and raw bytes, and jump/call targets get labels.
+ */
+pub fn disassemble(rom: &[u8], syntax: Syntax) -> String {
+    let instructions = trace(rom);
+
+    // Work out where each line of the listing starts, so that only
+    // targets that fall on the start of a line are given labels
+    let mut line_starts = BTreeSet::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        line_starts.insert(offset);
+        offset += match instructions.get(&offset) {
+            Some(instruction) => instruction.size() as usize,
+            None => 1,
+        };
+    }
+
+    let labels: BTreeSet<u16> = instructions
+        .values()
+        .filter_map(|instruction| match instruction {
+            Instruction::Jump(nnn) | Instruction::Call(nnn) | Instruction::JumpOffset(nnn) => {
+                Some(*nnn)
+            }
+            _ => None,
+        })
+        .filter(|target| {
+            let offset = target.wrapping_sub(PC_START_ADDRESS) as usize;
+            *target >= PC_START_ADDRESS && line_starts.contains(&offset)
+        })
+        .collect();
+
+    let address_name = |address: u16| match labels.contains(&address) {
+        true => label_name(address),
+        false => format!("{:#05x}", address),
+    };
+
+    let (comment, org, data) = match syntax {
+        Syntax::Cowgod => (";", "org", "db "),
+        Syntax::Octo => ("#", ":org", ""),
+    };
+
+    let mut listing = String::new();
+    listing.push_str(&format!("{} Disassembled by crab-chip\n", comment));
+    listing.push_str(&format!("{} {:#05x}\n", org, PC_START_ADDRESS));
+
+    for offset in line_starts {
+        let address = PC_START_ADDRESS + offset as u16;
+
+        if labels.contains(&address) {
+            match syntax {
+                Syntax::Cowgod => listing.push_str(&format!("\n{}:\n", label_name(address))),
+                Syntax::Octo => listing.push_str(&format!("\n: {}\n", label_name(address))),
+            }
+        }
+
+        let (text, bytes) = match instructions.get(&offset) {
+            Some(instruction) => {
+                let size = instruction.size() as usize;
+                let bytes: String = rom[offset..offset + size]
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect();
+                (instruction.format(syntax, &address_name), bytes)
+            }
+            // Data bytes are shown as pixels, since they're usually sprites
+            None => {
+                let byte = rom[offset];
+                let pixels: String = (0..8)
+                    .rev()
+                    .map(|bit| if byte & (1 << bit) != 0 { '#' } else { '.' })
+                    .collect();
+                (format!("{}{:#04x}", data, byte), pixels)
+            }
+        };
+
+        listing.push_str(&format!(
+            "    {:<28}{} {:#06x}  {}\n",
+            text, comment, address, bytes
+        ));
+    }
+
+    listing
+}
+
+fn label_name(address: u16) -> String {
+    format!("L{:03X}", address)
+}
+
+/**
+ * Follow every path of execution from the start of the program and
+ * return the instructions found, keyed by their offset into the ROM
+ */
+fn trace(rom: &[u8]) -> BTreeMap<usize, Instruction> {
+    let fetch = |address: u16| -> Option<Instruction> {
+        let offset = address.checked_sub(PC_START_ADDRESS)? as usize;
+        let word = |offset: usize| {
+            rom.get(offset..offset + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+
+        let instruction = Instruction::decode(word(offset)?, word(offset + 2).unwrap_or(0))?;
+        // A long instruction cut off by the end of the ROM isn't really one
+        if offset + instruction.size() as usize > rom.len() {
+            return None;
+        }
+        Some(instruction)
+    };
+
+    let mut instructions = BTreeMap::new();
+    let mut pending = vec![PC_START_ADDRESS];
+
+    while let Some(address) = pending.pop() {
+        let offset = address.wrapping_sub(PC_START_ADDRESS) as usize;
+        if instructions.contains_key(&offset) {
+            continue;
+        }
+
+        let instruction = match fetch(address) {
+            Some(instruction) => instruction,
+            None => continue,
+        };
+        instructions.insert(offset, instruction);
+
+        let next = address.wrapping_add(instruction.size());
+
+        match instruction {
+            Instruction::Jump(nnn) => pending.push(nnn),
+            Instruction::Call(nnn) => {
+                pending.push(nnn);
+                pending.push(next);
+            }
+            // Computed jumps and the end of a subroutine or the program
+            // can't be followed any further
+            Instruction::JumpOffset(_) | Instruction::Return | Instruction::Exit => {}
+            instruction if instruction.is_skip() => {
+                let skipped = fetch(next).map(|i| i.size()).unwrap_or(2);
+                pending.push(next);
+                pending.push(next.wrapping_add(skipped));
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    instructions
+}
@@ -0,0 +1,186 @@
+use crate::screenshot;
+use crate::theme::Palette;
+use crab_chip::{Chip8, Movie};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/**
+ * Options that only apply to headless mode
+ */
+#[derive(Default)]
+pub struct Options {
+    // Defaults to the length of the movie when there is one, or 600 frames
+    frames: Option<u64>,
+    until_pc: Option<u16>,
+    presses: Vec<KeyPress>,
+    output_path: Option<PathBuf>,
+}
+
+struct KeyPress {
+    key: usize,
+    first_frame: u64,
+    last_frame: u64,
+}
+
+impl Options {
+    pub fn parse_option(&mut self, option: &str, value: &str) -> Result<(), String> {
+        match option {
+            "--frames" => {
+                let parsed = value.parse();
+                self.frames = Some(parsed.map_err(|_| format!("Invalid frame count `{}`", value))?);
+            }
+            "--until-pc" => {
+                let parsed = u16::from_str_radix(value.trim_start_matches("0x"), 16);
+                self.until_pc = Some(parsed.map_err(|_| format!("Invalid address `{}`", value))?);
+            }
+            "--press" => self.presses.push(parse_press(value)?),
+            "--output" => self.output_path = Some(PathBuf::from(value)),
+            _ => return Err(format!("Unknown headless option `{}`", option)),
+        }
+        Ok(())
+    }
+}
+
+/**
+ * `crab-chip ROM --headless [OPTIONS]`
+ *
+ * Run a ROM without a window or audio, then print a hash of the final
+ * screen (and optionally save it as an image) so that the result can be
+ * compared between runs. Execution stops after the given number of frames,
+ * or earlier if the program exits or jumps to itself forever
+ */
+pub fn run(
+    mut chip8: Chip8,
+    options: &Options,
+    palette: Palette,
+    screenshot_scale: usize,
+    movie: Option<&Movie>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Options {
+        frames,
+        until_pc,
+        presses,
+        output_path,
+    } = options;
+
+    let frames = frames.unwrap_or_else(|| movie.map_or(600, |movie| movie.len() as u64));
+    let mut frame = 0;
+    let mut reason = "frame limit";
+
+    while frame < frames {
+        // Keys come from the movie while it lasts, and from `--press` otherwise
+        let from_movie = movie.is_some_and(|movie| movie.play_frame(frame as usize, &mut chip8));
+        if !from_movie {
+            for key in 0..16 {
+                let held = presses.iter().any(|press| {
+                    press.key == key && (press.first_frame..=press.last_frame).contains(&frame)
+                });
+                chip8.set_key(key, held);
+            }
+        }
+
+        let mut stop_reason = None;
+        let result = chip8.run_frame_until(|chip8| {
+            let pc = chip8.program_counter();
+            // A 1NNN that jumps to itself is how most programs halt
+            if chip8.next_opcode() == 0x1000 | pc {
+                stop_reason = Some("infinite loop");
+            } else if *until_pc == Some(pc) {
+                stop_reason = Some("reached PC");
+            }
+            stop_reason.is_some()
+        });
+        frame += 1;
+
+        if let Err(error) = result {
+            eprintln!("Crashed on frame {}", frame);
+            eprintln!("{:?}", chip8);
+            return Err(error.into());
+        }
+
+        if let Some(stop_reason) = stop_reason {
+            reason = stop_reason;
+            break;
+        }
+        if chip8.exit_flag {
+            reason = "program exited";
+            break;
+        }
+    }
+
+    let width = chip8.display_width();
+    let height = chip8.display_height();
+
+    println!(
+        "Stopped after {} frames ({}) at PC {:#06x}",
+        frame,
+        reason,
+        chip8.program_counter()
+    );
+    println!(
+        "Screen {}x{} sha1 {}",
+        width,
+        height,
+        sha1_smol::Sha1::from(chip8.framebuffer()).digest()
+    );
+
+    if let Some(output_path) = output_path {
+        match output_path.extension().and_then(|e| e.to_str()) {
+            Some("png") => screenshot::write_png(output_path, &chip8, palette, screenshot_scale)?,
+            Some("pbm") => write_pbm(output_path, &chip8)?,
+            _ => return Err("Output file must end in .png or .pbm".into()),
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Parse `KEY@FRAME` or `KEY@FIRST-LAST`, with KEY a hex digit
+ */
+fn parse_press(text: &str) -> Result<KeyPress, String> {
+    let invalid = || format!("Invalid key press `{}`, expected KEY@FRAME", text);
+
+    let (key, frames) = text.split_once('@').ok_or_else(invalid)?;
+    let key = usize::from_str_radix(key, 16).map_err(|_| invalid())?;
+    if key > 0xF {
+        return Err(invalid());
+    }
+
+    let (first_frame, last_frame) = match frames.split_once('-') {
+        Some((first, last)) => (first, last),
+        None => (frames, frames),
+    };
+
+    Ok(KeyPress {
+        key,
+        first_frame: first_frame.parse().map_err(|_| invalid())?,
+        last_frame: last_frame.parse().map_err(|_| invalid())?,
+    })
+}
+
+/**
+ * Write a binary PBM, where any pixel lit on either plane is black
+ */
+fn write_pbm(path: &Path, chip8: &Chip8) -> Result<(), Box<dyn std::error::Error>> {
+    let width = chip8.display_width();
+    let height = chip8.display_height();
+
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P4\n{} {}\n", width, height)?;
+
+    for row in chip8.framebuffer().chunks(width) {
+        let mut packed = vec![0x00; width.div_ceil(8)];
+        for (x, planes) in row.iter().enumerate() {
+            if *planes != 0 {
+                packed[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        file.write_all(&packed)?;
+    }
+
+    file.flush()?;
+
+    Ok(())
+}
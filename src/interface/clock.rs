@@ -0,0 +1,44 @@
+use super::osd::Osd;
+use crab_chip::{Chip8, Timing};
+use minifb::{Key, KeyRepeat, Window};
+
+/**
+ * Clock speeds stepped through with the hotkeys:
+ *
+ *  + / =   Faster
+ *  -       Slower
+ *
+ * Speeds set in the settings file don't have to be one of these,
+ * the hotkeys just move to the next one up or down
+ */
+const CLOCK_SPEEDS: [u64; 16] = [
+    60, 120, 240, 360, 480, 600, 720, 840, 1000, 1200, 1500, 2000, 3000, 5000, 10000, 20000,
+];
+
+pub fn handle_hotkeys(window: &Window, chip8: &mut Chip8, osd: &mut Osd) {
+    let pressed = |keys: [Key; 2]| {
+        keys.iter()
+            .any(|key| window.is_key_pressed(*key, KeyRepeat::Yes))
+    };
+
+    // The VIP's own timing decides how fast instructions run
+    if chip8.timing() == Timing::Vip {
+        return;
+    }
+
+    let current = chip8.clock_speed();
+
+    let new_speed = if pressed([Key::Equal, Key::NumPadPlus]) {
+        CLOCK_SPEEDS.iter().find(|speed| **speed > current)
+    } else if pressed([Key::Minus, Key::NumPadMinus]) {
+        CLOCK_SPEEDS.iter().rev().find(|speed| **speed < current)
+    } else {
+        return;
+    };
+
+    // Already at the fastest or slowest speed
+    let new_speed = new_speed.copied().unwrap_or(current);
+
+    chip8.set_clock_speed(new_speed);
+    osd.show(format!("CLOCK {} HZ", new_speed));
+}
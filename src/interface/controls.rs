@@ -1,8 +1,16 @@
-use minifb::Key;
+use minifb::{Key, KeyRepeat, Window};
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
 /**
- *  Controls are mapped as such on a standard
- *  QWERTY keyboard:
+ * Host keys for each key of the hex keypad, from 0 to F. Any of a key's
+ * host keys can be held to press it
+ */
+pub type Layout = [Vec<Key>; 16];
+
+/**
+ *  Unless remapped in the settings file, controls are mapped as such
+ *  on a standard QWERTY keyboard:
  *
  *  Chip8                    Computer
  *  Hex Keypad               Keyboard
@@ -16,24 +24,363 @@ use minifb::Key;
  *  |A|0|B|F|                |Z|X|C|V|
  *  +-+-+-+-+                +-+-+-+-+
  */
+const DEFAULT_LAYOUT: [Key; 16] = [
+    Key::X,    // 0
+    Key::Key1, // 1
+    Key::Key2, // 2
+    Key::Key3, // 3
+    Key::Q,    // 4
+    Key::W,    // 5
+    Key::E,    // 6
+    Key::A,    // 7
+    Key::S,    // 8
+    Key::D,    // 9
+    Key::Z,    // A
+    Key::C,    // B
+    Key::Key4, // C
+    Key::R,    // D
+    Key::F,    // E
+    Key::V,    // F
+];
+
+/**
+ * Names that host keys can be given by in the settings file. Keys
+ * with fixed uses, like the quick save keys, are left out. The keys
+ * used by the default hotkeys are named, so that hotkeys can be moved
+ * to them, but can't be mapped to the keypad while a hotkey uses them
+ */
+const KEY_NAMES: [(&str, Key); 78] = [
+    ("0", Key::Key0),
+    ("1", Key::Key1),
+    ("2", Key::Key2),
+    ("3", Key::Key3),
+    ("4", Key::Key4),
+    ("5", Key::Key5),
+    ("6", Key::Key6),
+    ("7", Key::Key7),
+    ("8", Key::Key8),
+    ("9", Key::Key9),
+    ("a", Key::A),
+    ("b", Key::B),
+    ("c", Key::C),
+    ("d", Key::D),
+    ("e", Key::E),
+    ("f", Key::F),
+    ("g", Key::G),
+    ("h", Key::H),
+    ("i", Key::I),
+    ("j", Key::J),
+    ("k", Key::K),
+    ("l", Key::L),
+    ("m", Key::M),
+    ("n", Key::N),
+    ("o", Key::O),
+    ("p", Key::P),
+    ("q", Key::Q),
+    ("r", Key::R),
+    ("s", Key::S),
+    ("t", Key::T),
+    ("u", Key::U),
+    ("v", Key::V),
+    ("w", Key::W),
+    ("x", Key::X),
+    ("y", Key::Y),
+    ("z", Key::Z),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("space", Key::Space),
+    ("enter", Key::Enter),
+    ("tab", Key::Tab),
+    ("left_shift", Key::LeftShift),
+    ("right_shift", Key::RightShift),
+    ("left_ctrl", Key::LeftCtrl),
+    ("right_ctrl", Key::RightCtrl),
+    ("left_alt", Key::LeftAlt),
+    ("right_alt", Key::RightAlt),
+    ("insert", Key::Insert),
+    ("delete", Key::Delete),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("page_up", Key::PageUp),
+    ("page_down", Key::PageDown),
+    ("comma", Key::Comma),
+    ("period", Key::Period),
+    ("slash", Key::Slash),
+    ("semicolon", Key::Semicolon),
+    ("apostrophe", Key::Apostrophe),
+    ("backslash", Key::Backslash),
+    ("left_bracket", Key::LeftBracket),
+    ("right_bracket", Key::RightBracket),
+    ("backquote", Key::Backquote),
+    ("numpad0", Key::NumPad0),
+    ("numpad1", Key::NumPad1),
+    ("numpad2", Key::NumPad2),
+    ("numpad3", Key::NumPad3),
+    ("numpad4", Key::NumPad4),
+    ("numpad5", Key::NumPad5),
+    ("numpad6", Key::NumPad6),
+    ("numpad7", Key::NumPad7),
+    ("numpad8", Key::NumPad8),
+    ("numpad9", Key::NumPad9),
+    ("f10", Key::F10),
+    ("f11", Key::F11),
+    ("f12", Key::F12),
+    ("pause", Key::Pause),
+];
+
+/**
+ * The `hotkeys` section of the settings file, for keys that control the
+ * emulator rather than the game
+ */
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Hotkeys {
+    pub pause: HostKey,
+    // Run a single frame while paused
+    pub frame_advance: HostKey,
+    pub reset: HostKey,
+    // Run faster while held
+    pub turbo: HostKey,
+    // Save the screen as a PNG next to the ROM
+    pub screenshot: HostKey,
+    // Start or stop recording an animated GIF
+    pub record: HostKey,
+    // Start or stop recording an input movie
+    pub movie: HostKey,
+    // Cycle through the colour themes
+    pub theme: HostKey,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Hotkeys {
+            pause: HostKey(Key::P),
+            frame_advance: HostKey(Key::N),
+            reset: HostKey(Key::F12),
+            turbo: HostKey(Key::Tab),
+            screenshot: HostKey(Key::F11),
+            record: HostKey(Key::G),
+            movie: HostKey(Key::M),
+            theme: HostKey(Key::F10),
+        }
+    }
+}
+
+impl Hotkeys {
+    /**
+     * Every hotkey with its name in the settings file
+     */
+    fn named(&self) -> [(&'static str, Key); 8] {
+        [
+            ("pause", self.pause.0),
+            ("frame_advance", self.frame_advance.0),
+            ("reset", self.reset.0),
+            ("turbo", self.turbo.0),
+            ("screenshot", self.screenshot.0),
+            ("record", self.record.0),
+            ("movie", self.movie.0),
+            ("theme", self.theme.0),
+        ]
+    }
+
+    fn hotkey_for(&self, key: Key) -> Option<&'static str> {
+        self.named()
+            .iter()
+            .find(|(_, hotkey)| *hotkey == key)
+            .map(|(name, _)| *name)
+    }
+}
+
+impl HostKey {
+    pub fn is_pressed(self, window: &Window) -> bool {
+        window.is_key_pressed(self.0, KeyRepeat::No)
+    }
 
-pub fn get_keyboard_layout() -> [Key; 16] {
-    [
-        Key::X,    // 0
-        Key::Key1, // 1
-        Key::Key2, // 2
-        Key::Key3, // 3
-        Key::Q,    // 4
-        Key::W,    // 5
-        Key::E,    // 6
-        Key::A,    // 7
-        Key::S,    // 8
-        Key::D,    // 9
-        Key::Z,    // A
-        Key::C,    // B
-        Key::Key4, // C
-        Key::R,    // D
-        Key::F,    // E
-        Key::V,    // F
-    ]
+    pub fn is_down(self, window: &Window) -> bool {
+        window.is_key_down(self.0)
+    }
+}
+
+/**
+ * Host keys added for the ROM database's key hints, so that games that
+ * use the keypad for directions can be played with the arrow keys
+ */
+const HINT_KEYS: [(&str, Key); 6] = [
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("a", Key::Space),
+    ("b", Key::Enter),
+];
+
+/**
+ * A `keys` section of the settings file, mapping hex keypad keys to
+ * one or more host keys, e.g.
+ *
+ *  keys:
+ *    5: [w, up]
+ *    8: s
+ *
+ * Keypad keys that aren't listed keep their current mapping
+ */
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+#[serde(transparent)]
+pub struct Bindings(HashMap<KeypadKey, HostKeys>);
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "Name")]
+struct KeypadKey(usize);
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(try_from = "Name")]
+pub struct HostKey(Key);
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(try_from = "OneOrMany")]
+struct HostKeys(Vec<Key>);
+
+/**
+ * A key name as written in the settings file. YAML reads digits as
+ * numbers, so those are accepted too
+ */
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Name {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(Name),
+    Many(Vec<Name>),
+}
+
+impl From<Name> for String {
+    fn from(name: Name) -> Self {
+        match name {
+            Name::Number(number) => number.to_string(),
+            Name::Text(text) => text,
+        }
+    }
+}
+
+impl TryFrom<Name> for KeypadKey {
+    type Error = String;
+
+    fn try_from(name: Name) -> Result<Self, Self::Error> {
+        let name = String::from(name);
+        match (name.len(), usize::from_str_radix(&name, 16)) {
+            (1, Ok(key)) => Ok(KeypadKey(key)),
+            _ => Err(format!(
+                "Invalid keypad key `{}`, expected 0-9 or A-F",
+                name
+            )),
+        }
+    }
+}
+
+impl TryFrom<Name> for HostKey {
+    type Error = String;
+
+    fn try_from(name: Name) -> Result<Self, Self::Error> {
+        let name = String::from(name).to_lowercase();
+        KEY_NAMES
+            .iter()
+            .find(|(key_name, _)| *key_name == name)
+            .map(|(_, key)| HostKey(*key))
+            .ok_or_else(|| format!("Unknown key `{}`", name))
+    }
+}
+
+impl TryFrom<OneOrMany> for HostKeys {
+    type Error = String;
+
+    fn try_from(names: OneOrMany) -> Result<Self, Self::Error> {
+        let names = match names {
+            OneOrMany::One(name) => vec![name],
+            OneOrMany::Many(names) => names,
+        };
+
+        let keys = names
+            .into_iter()
+            .map(|name| HostKey::try_from(name).map(|HostKey(key)| key))
+            .collect::<Result<Vec<Key>, String>>()?;
+
+        Ok(HostKeys(keys))
+    }
+}
+
+/**
+ * The default layout with the settings file's `keys` applied, then the
+ * ROM database's key hints for the game, then the game's own entry in
+ * `rom_keys` if it has one. A key can't be on both the keypad and a
+ * hotkey, since pressing it would do both
+ */
+pub fn get_keyboard_layout(
+    keys: &Bindings,
+    hints: Option<&HashMap<String, u8>>,
+    rom_keys: Option<&Bindings>,
+    hotkeys: &Hotkeys,
+) -> Result<Layout, String> {
+    let mut layout: Layout = Default::default();
+    for (host_keys, key) in layout.iter_mut().zip(DEFAULT_LAYOUT.iter()) {
+        host_keys.push(*key);
+    }
+
+    apply_bindings(&mut layout, keys);
+
+    // Hints are added alongside the keys already mapped, rather than replacing
+    // them, and are left out where a hotkey has been moved onto them
+    for (hint, keypad_key) in hints.into_iter().flatten() {
+        let host_key = HINT_KEYS
+            .iter()
+            .find(|(name, key)| name == hint && hotkeys.hotkey_for(*key).is_none());
+        if let (Some((_, host_key)), Some(host_keys)) =
+            (host_key, layout.get_mut(*keypad_key as usize))
+        {
+            host_keys.push(*host_key);
+        }
+    }
+
+    if let Some(rom_keys) = rom_keys {
+        apply_bindings(&mut layout, rom_keys);
+    }
+
+    for (keypad_key, host_keys) in layout.iter().enumerate() {
+        for host_key in host_keys {
+            if let Some(hotkey) = hotkeys.hotkey_for(*host_key) {
+                let name = KEY_NAMES
+                    .iter()
+                    .find(|(_, key)| key == host_key)
+                    .map_or("?", |(name, _)| *name);
+                return Err(format!(
+                    "Key `{}` is mapped to both keypad key {:X} and the `{}` hotkey. Change one of them in settings.yaml",
+                    name, keypad_key, hotkey
+                ));
+            }
+        }
+    }
+
+    Ok(layout)
+}
+
+fn apply_bindings(layout: &mut Layout, bindings: &Bindings) {
+    for (KeypadKey(key), HostKeys(host_keys)) in &bindings.0 {
+        layout[*key] = host_keys.clone();
+    }
+}
+
+/**
+ * Which keys of the hex keypad are being held down
+ */
+pub fn held_keys(window: &Window, layout: &Layout) -> Vec<bool> {
+    layout
+        .iter()
+        .map(|host_keys| host_keys.iter().any(|key| window.is_key_down(*key)))
+        .collect()
 }
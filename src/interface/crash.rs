@@ -0,0 +1,60 @@
+use super::text;
+use crab_chip::{Chip8, Error};
+
+const BACKGROUND: u32 = 0x00_400000;
+const FOREGROUND: u32 = 0x00_FFFFFF;
+const DIM: u32 = 0x00_C08080;
+
+// Only this many lines of the error message fit above the machine state
+const MAX_MESSAGE_LINES: usize = 3;
+
+/**
+ * Replace the screen with the error that stopped the program and the
+ * state of the machine when it happened
+ */
+pub fn draw(framebuffer: &mut [u32], width: usize, chip8: &Chip8, error: &Error) {
+    framebuffer.iter_mut().for_each(|pixel| *pixel = BACKGROUND);
+
+    let columns = width / text::CHAR_WIDTH;
+    let mut lines: Vec<(String, u32)> = vec![("CRASHED".to_string(), FOREGROUND)];
+
+    for line in text::wrap(&error.to_string(), columns)
+        .into_iter()
+        .take(MAX_MESSAGE_LINES)
+    {
+        lines.push((line, DIM));
+    }
+
+    lines.push((
+        format!(
+            "PC {:04X} OP {:04X} I {:04X} SP {}",
+            chip8.program_counter(),
+            chip8.opcode(),
+            chip8.index_register(),
+            chip8.stack_pointer()
+        ),
+        FOREGROUND,
+    ));
+
+    for (row, registers) in chip8.registers().chunks(4).enumerate() {
+        let line: Vec<String> = registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{:X} {:02X}", row * 4 + i, value))
+            .collect();
+        lines.push((line.join(" "), FOREGROUND));
+    }
+
+    lines.push(("BACKSPACE REWIND  ESC QUIT".to_string(), DIM));
+
+    for (i, (line, colour)) in lines.iter().enumerate() {
+        text::draw(
+            framebuffer,
+            width,
+            1,
+            1 + i * text::LINE_HEIGHT,
+            line,
+            *colour,
+        );
+    }
+}
@@ -0,0 +1,435 @@
+use crab_chip::{AccessKind, Chip8, Instruction};
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const HELP: &str = "\
+Commands:
+  c, continue            Resume execution
+  p, pause               Pause execution
+  s, step                Execute a single instruction
+  n, next                Step over a 2NNN subroutine call
+  o, out                 Run until the current subroutine returns (00EE)
+  b, break ADDR          Break when PC reaches ADDR
+  w, watch ADDR [r|w|rw] Break when ADDR is read and/or written (default: w)
+  bif REG OP VALUE       Break when a register condition holds, e.g. `bif vF == 1`
+                         REG is v0-vF or i, OP is one of == != < <= > >=
+  d, delete [N]          Delete breakpoint N, or every breakpoint
+  l, list                List breakpoints
+  r, regs                Print the machine state
+  x ADDR [LEN]           Dump LEN bytes of memory starting at ADDR
+  h, help                Show this message
+Numbers are hexadecimal, with or without a 0x prefix, unless VALUE starts with #";
+
+/**
+ * Terminal driven step debugger.
+ *
+ * Commands are read from stdin on a separate thread so the window keeps
+ * rendering while the emulator is paused or waiting for input.
+ */
+pub struct Debugger {
+    commands: Receiver<String>,
+    state: RunState,
+    breakpoints: Vec<Breakpoint>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RunState {
+    Running,
+    Paused,
+    // Pause after the next instruction
+    Step,
+    // Pause when execution is back at `return_address` with the same stack depth
+    StepOver {
+        return_address: u16,
+        stack_pointer: u8,
+    },
+    // Pause once the stack is shallower than `stack_pointer`
+    StepOut {
+        stack_pointer: u8,
+    },
+}
+
+enum Breakpoint {
+    Address(u16),
+    Watch {
+        address: usize,
+        read: bool,
+        write: bool,
+    },
+    // Only breaks when the condition goes from false to true, so that
+    // execution can be continued while the condition still holds
+    Condition {
+        register: Register,
+        op: Comparison,
+        value: u16,
+        held: bool,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum Register {
+    V(usize),
+    I,
+}
+
+#[derive(Clone, Copy)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        let (sender, commands) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                match line {
+                    Ok(line) => {
+                        if sender.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        println!("Debugger attached. Type `help` for a list of commands.");
+        prompt();
+
+        Debugger {
+            commands,
+            state: RunState::Running,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /**
+     * Handle any commands typed since the last frame
+     */
+    pub fn poll_commands(&mut self, chip8: &Chip8) {
+        while let Ok(line) = self.commands.try_recv() {
+            if let Err(e) = self.run_command(line.trim(), chip8) {
+                println!("{}", e);
+            }
+            // Steps print their own prompt once they've finished
+            if let RunState::Running | RunState::Paused = self.state {
+                prompt();
+            }
+        }
+    }
+
+    /**
+     * Whether the emulator may execute another instruction
+     */
+    pub fn can_run(&self) -> bool {
+        self.state != RunState::Paused
+    }
+
+    /**
+     * Called after every emulated cycle to check breakpoints and step progress
+     */
+    pub fn after_cycle(&mut self, chip8: &Chip8) {
+        let pc = chip8.program_counter();
+        let sp = chip8.stack_pointer();
+
+        let step_finished = match self.state {
+            RunState::Running | RunState::Paused => false,
+            RunState::Step => true,
+            RunState::StepOver {
+                return_address,
+                stack_pointer,
+            } => pc == return_address && sp == stack_pointer,
+            RunState::StepOut { stack_pointer } => sp < stack_pointer,
+        };
+
+        if step_finished {
+            self.pause(chip8, None);
+            return;
+        }
+
+        let mut hit = None;
+
+        for (index, breakpoint) in self.breakpoints.iter_mut().enumerate() {
+            let triggered = match breakpoint {
+                Breakpoint::Address(address) => pc == *address,
+                Breakpoint::Watch {
+                    address,
+                    read,
+                    write,
+                } => chip8.memory_accesses().iter().any(|access| {
+                    let kind_matches = match access.kind {
+                        AccessKind::Read => *read,
+                        AccessKind::Write => *write,
+                    };
+                    kind_matches && (access.address..access.address + access.len).contains(address)
+                }),
+                Breakpoint::Condition {
+                    register,
+                    op,
+                    value,
+                    held,
+                } => {
+                    let current = match register {
+                        Register::V(x) => chip8.registers()[*x] as u16,
+                        Register::I => chip8.index_register(),
+                    };
+                    let was_held = *held;
+                    *held = op.holds(current, *value);
+                    *held && !was_held
+                }
+            };
+
+            if triggered && hit.is_none() {
+                hit = Some(index);
+            }
+        }
+
+        if let Some(index) = hit {
+            self.pause(chip8, Some(index));
+        }
+    }
+
+    fn pause(&mut self, chip8: &Chip8, breakpoint: Option<usize>) {
+        if let Some(index) = breakpoint {
+            println!();
+            println!("Hit breakpoint {}: {}", index, self.breakpoints[index]);
+        }
+        self.state = RunState::Paused;
+        print_location(chip8);
+        prompt();
+    }
+
+    fn run_command(&mut self, line: &str, chip8: &Chip8) -> Result<(), String> {
+        let mut args = line.split_whitespace();
+        let command = match args.next() {
+            Some(command) => command,
+            None => return Ok(()),
+        };
+        let args: Vec<&str> = args.collect();
+
+        match command {
+            "c" | "continue" => self.state = RunState::Running,
+            "p" | "pause" => {
+                self.state = RunState::Paused;
+                print_location(chip8);
+            }
+            "s" | "step" => self.state = RunState::Step,
+            "n" | "next" => {
+                // Only calls need stepping over, anything else is a single step
+                self.state = match chip8.next_opcode() & 0xF000 {
+                    0x2000 => RunState::StepOver {
+                        return_address: chip8.program_counter().wrapping_add(2),
+                        stack_pointer: chip8.stack_pointer(),
+                    },
+                    _ => RunState::Step,
+                };
+            }
+            "o" | "out" => {
+                if chip8.stack_pointer() == 0 {
+                    return Err("Not inside a subroutine".to_string());
+                }
+                self.state = RunState::StepOut {
+                    stack_pointer: chip8.stack_pointer(),
+                };
+            }
+            "b" | "break" => {
+                let address = parse_number(args.first().ok_or("Usage: break ADDR")?)?;
+                self.add_breakpoint(Breakpoint::Address(address));
+            }
+            "w" | "watch" => {
+                let address = parse_number(args.first().ok_or("Usage: watch ADDR [r|w|rw]")?)?;
+                let (read, write) = match args.get(1).copied().unwrap_or("w") {
+                    "r" => (true, false),
+                    "w" => (false, true),
+                    "rw" => (true, true),
+                    mode => return Err(format!("Unknown watch mode `{}`", mode)),
+                };
+                self.add_breakpoint(Breakpoint::Watch {
+                    address: address as usize,
+                    read,
+                    write,
+                });
+            }
+            "bif" => {
+                if args.len() != 3 {
+                    return Err("Usage: bif REG OP VALUE".to_string());
+                }
+                let register = parse_register(args[0])?;
+                let op = parse_comparison(args[1])?;
+                let value = parse_number(args[2])?;
+                self.add_breakpoint(Breakpoint::Condition {
+                    register,
+                    op,
+                    value,
+                    held: false,
+                });
+            }
+            "d" | "delete" => match args.first() {
+                Some(index) => {
+                    let index: usize = index.parse().map_err(|_| "Invalid breakpoint number")?;
+                    if index >= self.breakpoints.len() {
+                        return Err(format!("No breakpoint {}", index));
+                    }
+                    self.breakpoints.remove(index);
+                }
+                None => self.breakpoints.clear(),
+            },
+            "l" | "list" => {
+                for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+                    println!("{}: {}", index, breakpoint);
+                }
+            }
+            "r" | "regs" => println!("{:?}", chip8),
+            "x" => {
+                let address = parse_number(args.first().ok_or("Usage: x ADDR [LEN]")?)? as usize;
+                let len = match args.get(1) {
+                    Some(len) => parse_number(len)? as usize,
+                    None => 0x10,
+                };
+                let memory = chip8.memory();
+                let end = (address + len).min(memory.len());
+                if address >= end {
+                    return Err("Address is outside of memory".to_string());
+                }
+                for (row, bytes) in memory[address..end].chunks(16).enumerate() {
+                    println!("{:#06x}: {:02x?}", address + row * 16, bytes);
+                }
+            }
+            "h" | "help" => println!("{}", HELP),
+            _ => {
+                return Err(format!(
+                    "Unknown command `{}`. Type `help` for a list of commands",
+                    command
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        println!("Breakpoint {}: {}", self.breakpoints.len(), breakpoint);
+        self.breakpoints.push(breakpoint);
+    }
+}
+
+impl Comparison {
+    fn holds(self, a: u16, b: u16) -> bool {
+        match self {
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Gt => a > b,
+            Comparison::Ge => a >= b,
+        }
+    }
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breakpoint::Address(address) => write!(f, "PC == {:#06x}", address),
+            Breakpoint::Watch {
+                address,
+                read,
+                write,
+            } => {
+                let mode = match (read, write) {
+                    (true, true) => "read/write",
+                    (true, false) => "read",
+                    _ => "write",
+                };
+                write!(f, "{} of {:#06x}", mode, address)
+            }
+            Breakpoint::Condition {
+                register,
+                op,
+                value,
+                ..
+            } => {
+                let register = match register {
+                    Register::V(x) => format!("v{:X}", x),
+                    Register::I => "I".to_string(),
+                };
+                let op = match op {
+                    Comparison::Eq => "==",
+                    Comparison::Ne => "!=",
+                    Comparison::Lt => "<",
+                    Comparison::Le => "<=",
+                    Comparison::Gt => ">",
+                    Comparison::Ge => ">=",
+                };
+                write!(f, "{} {} {:#x}", register, op, value)
+            }
+        }
+    }
+}
+
+fn print_location(chip8: &Chip8) {
+    let pc = chip8.program_counter() as usize;
+    let next_word = match chip8.memory().get(pc + 2..pc + 4) {
+        Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+        None => 0x0000,
+    };
+    let mnemonic = match Instruction::decode(chip8.next_opcode(), next_word) {
+        Some(instruction) => instruction.to_string(),
+        None => "???".to_string(),
+    };
+
+    println!(
+        "Paused at {:#06x}: {:04x}  {}",
+        pc,
+        chip8.next_opcode(),
+        mnemonic
+    );
+}
+
+fn prompt() {
+    print!("(crab-chip) ");
+    io::stdout().flush().ok();
+}
+
+/**
+ * Parse a hexadecimal number, or a decimal one when prefixed with `#`
+ */
+fn parse_number(text: &str) -> Result<u16, String> {
+    let parsed = match text.strip_prefix('#') {
+        Some(decimal) => decimal.parse(),
+        None => u16::from_str_radix(text.trim_start_matches("0x"), 16),
+    };
+    parsed.map_err(|_| format!("Invalid number `{}`", text))
+}
+
+fn parse_register(text: &str) -> Result<Register, String> {
+    let lower = text.to_lowercase();
+    if lower == "i" {
+        return Ok(Register::I);
+    }
+    match lower
+        .strip_prefix('v')
+        .map(|x| usize::from_str_radix(x, 16))
+    {
+        Some(Ok(x)) if x < 16 => Ok(Register::V(x)),
+        _ => Err(format!("Unknown register `{}`", text)),
+    }
+}
+
+fn parse_comparison(text: &str) -> Result<Comparison, String> {
+    match text {
+        "==" => Ok(Comparison::Eq),
+        "!=" => Ok(Comparison::Ne),
+        "<" => Ok(Comparison::Lt),
+        "<=" => Ok(Comparison::Le),
+        ">" => Ok(Comparison::Gt),
+        ">=" => Ok(Comparison::Ge),
+        _ => Err(format!("Unknown comparison `{}`", text)),
+    }
+}
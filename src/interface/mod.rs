@@ -1,40 +1,114 @@
+mod clock;
 mod controls;
+mod crash;
+mod debugger;
+mod movie;
+mod osd;
+mod quick_save;
+mod recorder;
 mod sound;
-use crate::core::Chip8;
-use crate::utils;
+mod text;
+use crate::database::RomInfo;
+use crate::screenshot;
+use crate::theme::Themes;
 use crate::Config;
-use crate::CLOCK_SPEED_HZ;
-use crate::DEBUG;
-use crate::TARGET_FPS;
-use crate::VIDEO_HEIGHT;
-use crate::VIDEO_WIDTH;
+pub use controls::{Bindings, Hotkeys};
+use crab_chip::{Chip8, Error, Movie, RewindBuffer};
+use crab_chip::{HIRES_VIDEO_HEIGHT, HIRES_VIDEO_WIDTH, TARGET_FPS};
+use debugger::Debugger;
 use minifb::{Key, Scale, Window, WindowOptions};
-use spin_sleep;
+use movie::MovieMode;
+use osd::Osd;
+use recorder::Recorder;
+use sound::Audio;
+pub use sound::Waveform;
+use std::fs;
 use std::path::Path;
 use std::time::Duration;
 
-const CYCLES_PER_FRAME: u64 = CLOCK_SPEED_HZ / TARGET_FPS;
-
-pub fn render(mut chip8: Chip8, settings: &Config) -> Result<(), Box<dyn std::error::Error>> {
+pub fn render(
+    mut chip8: Chip8,
+    settings: &Config,
+    rom_path: &Path,
+    mut themes: Themes,
+    rom_info: Option<&RomInfo>,
+    playback: Option<Movie>,
+) -> Result<(), Box<dyn std::error::Error>> {
     let opts = WindowOptions {
-        scale: Scale::X16,
+        scale: window_scale(settings.scale)?,
         ..WindowOptions::default()
     };
 
-    let window_title = format!(
-        "{} - Crab Chip",
-        Path::new(&settings.rom_name)
+    // Games in the ROM database are shown by their title, and others by file name
+    let game = match rom_info {
+        Some(rom_info) => rom_info.description(),
+        None => rom_path
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("Unknown")
-    );
+            .to_string(),
+    };
+    let window_title = format!("{} - Crab Chip", game);
+
+    // The window is always sized for SUPER-CHIP's high resolution mode.
+    // Low resolution frames are scaled up to fill it
+    let mut window = Window::new(&window_title, HIRES_VIDEO_WIDTH, HIRES_VIDEO_HEIGHT, opts)?;
+
+    let mut framebuffer: Vec<u32> = vec![0; HIRES_VIDEO_WIDTH * HIRES_VIDEO_HEIGHT];
+
+    // Keyboard controls, which can be remapped for this game in particular
+    let rom_hash = chip8.rom_hash();
+    let rom_keys = settings
+        .rom_keys
+        .iter()
+        .find(|(hash, _)| hash.eq_ignore_ascii_case(&rom_hash))
+        .map(|(_, bindings)| bindings);
+    let keyboard_controls = controls::get_keyboard_layout(
+        &settings.keys,
+        rom_info.map(RomInfo::key_hints),
+        rom_keys,
+        &settings.hotkeys,
+    )?;
+
+    // One save state per frame, for playing the game backwards while backspace is held
+    let mut rewind = RewindBuffer::new((settings.rewind_seconds * TARGET_FPS) as usize);
+
+    let mut debugger = match settings.debugger {
+        true => Some(Debugger::new()),
+        false => None,
+    };
+
+    // The error that stopped the program, if it has crashed. The crash screen
+    // stays up until the game is rewound or a save state is loaded
+    let mut crash: Option<Error> = None;
+
+    let mut osd = Osd::default();
+
+    // Paused with the pause hotkey, as opposed to by the debugger
+    let mut paused = false;
 
-    let mut window = Window::new(&window_title, VIDEO_WIDTH, VIDEO_HEIGHT, opts)?;
+    // Animated GIF of the game, while one is being recorded
+    let mut recorder: Option<Recorder> = None;
 
-    let mut framebuffer: Vec<u32> = vec![0; VIDEO_WIDTH * VIDEO_HEIGHT];
+    // Input movie being recorded or played back, which starts off playing
+    // if one was given on the command line
+    let mut movie = playback.map(|movie| MovieMode::Playing { movie, frame: 0 });
 
-    // Keyboard controls
-    let keyboard_controls = controls::get_keyboard_layout();
+    // Whether the debugger stopped the program partway through a frame, which
+    // is finished before another is started
+    let mut mid_frame = false;
+
+    // Carry on without sound rather than refuse to start on machines without an audio device
+    let audio = match settings.sound_enabled {
+        true => Audio::new(
+            settings.sound_frequency,
+            settings.sound_waveform,
+            settings.sound_volume,
+        )
+        .map_err(|e| eprintln!("Sound is unavailable: {}", e))
+        .ok(),
+        false => None,
+    };
 
     // Unfortunately, due to cross platfrom differences, thread::sleep appears to
     // be unreliable on Windows, cutting the FPS in half. Because of this we must call
@@ -42,61 +116,341 @@ pub fn render(mut chip8: Chip8, settings: &Config) -> Result<(), Box<dyn std::er
     // window.limit_update_rate
     window.limit_update_rate(None);
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
-        // Send keyboard info to emulated keypad
-        let held_keys: Vec<bool> = keyboard_controls
-            .iter()
-            .map(|key| window.is_key_down(*key))
-            .collect();
-
-        chip8.set_keys(held_keys);
-
-        let mut should_play_sound = false;
+    while window.is_open() && !window.is_key_down(Key::Escape) && !chip8.exit_flag {
+        // Keyboard info for the emulated keypad, unless a movie is playing
+        let held_keys = controls::held_keys(&window, &keyboard_controls);
 
         // Even though we already have a flag indicating whether to draw
         // within `chip8`, it's clock cycle is too fast for the event loop to pick up.
         // For this reason, we have an independant draw flag here for the event loop
         let mut should_draw = false;
 
-        for _ in 0..CYCLES_PER_FRAME {
-            chip8.emulate_cycle()?;
-            // If any of the cpu cycles in this frame requested to draw,
-            // set the flag in the event loop
-            if chip8.draw_flag {
-                should_draw = true
+        if quick_save::handle_hotkeys(&window, &mut chip8, rom_path) {
+            crash = None;
+            mid_frame = false;
+            should_draw = true;
+            // Movies can only carry on from the frame before
+            if let Some(movie) = movie.take() {
+                movie.finish(rom_path, &mut osd);
+            }
+        }
+
+        // Movies are played back at the speed they were recorded at
+        if movie.is_none() {
+            clock::handle_hotkeys(&window, &mut chip8, &mut osd);
+        }
+
+        if settings.hotkeys.theme.is_pressed(&window) {
+            themes.next();
+            osd.show(format!("THEME {}", themes.name()));
+        }
+
+        // The on-screen display is drawn over the game, so the game has to be
+        // redrawn underneath it while it's up
+        if osd.update() {
+            should_draw = true;
+        }
+
+        if let Some(debugger) = debugger.as_mut() {
+            debugger.poll_commands(&chip8);
+        }
+
+        let hotkeys = settings.hotkeys;
+
+        if hotkeys.pause.is_pressed(&window) {
+            paused = !paused;
+            osd.show(
+                match paused {
+                    true => "PAUSED",
+                    false => "RESUMED",
+                }
+                .to_string(),
+            );
+        }
+
+        if hotkeys.reset.is_pressed(&window) {
+            if let Some(movie) = movie.take() {
+                movie.finish(rom_path, &mut osd);
+            }
+            match reset(&mut chip8, rom_path) {
+                Ok(()) => {
+                    crash = None;
+                    mid_frame = false;
+                    rewind.clear();
+                    should_draw = true;
+                    osd.show("RESET".to_string());
+                }
+                Err(e) => eprintln!("Can't reload {}: {}", rom_path.display(), e),
+            }
+        }
+
+        if hotkeys.movie.is_pressed(&window) {
+            match movie.take() {
+                Some(movie) => movie.finish(rom_path, &mut osd),
+                // Recordings start from a freshly reset machine, so that
+                // they can be played back from the same point
+                None => match reset(&mut chip8, rom_path) {
+                    Ok(()) => {
+                        crash = None;
+                        mid_frame = false;
+                        rewind.clear();
+                        should_draw = true;
+                        movie = Some(MovieMode::Recording(Movie::new(&chip8)));
+                        osd.show("RECORDING MOVIE".to_string());
+                    }
+                    Err(e) => eprintln!("Can't reload {}: {}", rom_path.display(), e),
+                },
             }
-            if chip8.sound_flag {
-                should_play_sound = true;
+        }
+
+        if hotkeys.screenshot.is_pressed(&window) {
+            let path = screenshot::timestamped_path(rom_path, "png");
+            let scale = settings.screenshot_scale;
+            match screenshot::write_png(&path, &chip8, themes.palette(), scale) {
+                Ok(()) => osd.show("SCREENSHOT SAVED".to_string()),
+                Err(e) => {
+                    eprintln!("Can't save screenshot {}: {}", path.display(), e);
+                    osd.show("SCREENSHOT FAILED".to_string());
+                }
             }
         }
 
-        if DEBUG {
-            utils::clear_terminal();
-            println!("{:?}", chip8);
+        if hotkeys.record.is_pressed(&window) {
+            recorder = match recorder.take() {
+                Some(recording) => {
+                    finish_recording(recording, &mut osd);
+                    None
+                }
+                None => {
+                    let path = screenshot::timestamped_path(rom_path, "gif");
+                    match Recorder::start(&path, settings) {
+                        Ok(recording) => {
+                            osd.show("RECORDING".to_string());
+                            Some(recording)
+                        }
+                        Err(e) => {
+                            eprintln!("Can't record to {}: {}", path.display(), e);
+                            osd.show("RECORDING FAILED".to_string());
+                            None
+                        }
+                    }
+                }
+            };
+        }
+
+        let rewinding = window.is_key_down(Key::Backspace);
+
+        // How many frames of the game to run before this one is shown
+        let frames = match (paused, hotkeys.turbo.is_down(&window)) {
+            (true, _) => hotkeys.frame_advance.is_pressed(&window) as u64,
+            (false, true) => settings.turbo_speed,
+            (false, false) => 1,
+        };
+
+        if rewinding {
+            // Replace this frame with the one before it instead of running the game
+            if let Some(state) = rewind.pop() {
+                // A failed rewind shouldn't end the game, so just report it
+                // and start the history over from here
+                match chip8.load_state(&state) {
+                    Ok(()) => {
+                        crash = None;
+                        mid_frame = false;
+                        should_draw = true;
+                        if let Some(movie) = movie.take() {
+                            movie.finish(rom_path, &mut osd);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Can't rewind: {}", e);
+                        osd.show("REWIND FAILED".to_string());
+                        rewind.clear();
+                    }
+                }
+            }
+        } else if crash.is_none() {
+            for _ in 0..frames {
+                // A frame that the debugger stopped partway through carries
+                // on with the keys it started with
+                if !mid_frame {
+                    let playing = match movie.as_mut() {
+                        Some(movie) => movie.next_frame(&mut chip8, &held_keys),
+                        None => {
+                            chip8.set_keys(&held_keys);
+                            true
+                        }
+                    };
+                    // Hand control back to the player at the end of a movie
+                    if !playing {
+                        if let Some(movie) = movie.take() {
+                            movie.finish(rom_path, &mut osd);
+                        }
+                        chip8.set_keys(&held_keys);
+                    }
+                }
+
+                match run_frame(&mut chip8, &mut debugger, &mut mid_frame) {
+                    Ok(drawn) => should_draw |= drawn,
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        eprintln!("{:?}", chip8);
+                        crash = Some(error);
+                        should_draw = true;
+                        break;
+                    }
+                }
+
+                // Only whole frames are recorded, so that movies and rewinding
+                // replay the same as the game ran
+                if mid_frame {
+                    break;
+                }
+                if let Some(movie) = movie.as_mut() {
+                    movie.end_frame(&chip8);
+                }
+                rewind.push(chip8.save_state());
+            }
         }
 
-        // Dump video ram data into frame buffer
         if should_draw {
-            for (framebuffer_pixel, vram_pixel) in framebuffer.iter_mut().zip(chip8.gfx.iter()) {
-                match *vram_pixel {
-                    0xFF => *framebuffer_pixel = 0x00_FFFFFF,
-                    _ => *framebuffer_pixel = 0x00_000000,
+            match &crash {
+                Some(error) => crash::draw(&mut framebuffer, HIRES_VIDEO_WIDTH, &chip8, error),
+                // Dump video ram data into frame buffer
+                None => {
+                    let scale_x = HIRES_VIDEO_WIDTH / chip8.display_width();
+                    let scale_y = HIRES_VIDEO_HEIGHT / chip8.display_height();
+                    let palette = themes.palette();
+
+                    for (i, framebuffer_pixel) in framebuffer.iter_mut().enumerate() {
+                        let x = (i % HIRES_VIDEO_WIDTH) / scale_x;
+                        let y = (i / HIRES_VIDEO_WIDTH) / scale_y;
+
+                        let planes = chip8.framebuffer()[y * chip8.display_width() + x];
+                        *framebuffer_pixel = palette[planes as usize & 0b11];
+                    }
                 }
             }
+
+            osd.draw(&mut framebuffer, HIRES_VIDEO_WIDTH);
+        }
+
+        // The buzzer sounds for as long as the sound timer is running, but not
+        // while the game is paused, rewinding or crashed
+        let halted = paused || debugger.as_ref().is_some_and(|d| !d.can_run());
+        let sound_active = chip8.is_sound_active() && !halted && !rewinding && crash.is_none();
+        if let Some(audio) = audio.as_ref() {
+            // XO-CHIP programs can replace the beep with their own audio pattern
+            audio.update(sound_active, chip8.audio_pattern());
         }
 
-        if should_play_sound && settings.sound_enabled {
-            std::thread::spawn(|| {
-                sound::beep();
-            });
+        if let Some(recording) = recorder.as_mut() {
+            if let Err(e) = recording.capture(&chip8, themes.palette(), sound_active) {
+                eprintln!("Recording stopped: {}", e);
+                osd.show("RECORDING FAILED".to_string());
+                recorder = None;
+            }
         }
 
         // Fail whole render if update fails. Real applications may want to handle this in a different way
-        window.update_with_buffer(&framebuffer, VIDEO_WIDTH, VIDEO_HEIGHT)?;
+        window.update_with_buffer(&framebuffer, HIRES_VIDEO_WIDTH, HIRES_VIDEO_HEIGHT)?;
 
         // Limit to max fps
         spin_sleep::sleep(Duration::from_millis(1000 / TARGET_FPS));
     }
 
+    if let Some(recording) = recorder {
+        finish_recording(recording, &mut osd);
+    }
+    if let Some(movie) = movie {
+        movie.finish(rom_path, &mut osd);
+    }
+
     Ok(())
 }
+
+fn finish_recording(recording: Recorder, osd: &mut Osd) {
+    match recording.finish() {
+        Ok(path) => {
+            println!("Saved recording to {}", path.display());
+            osd.show("RECORDING SAVED".to_string());
+        }
+        Err(e) => {
+            eprintln!("Can't finish recording: {}", e);
+            osd.show("RECORDING FAILED".to_string());
+        }
+    }
+}
+
+/**
+ * Switch the machine off and on again, reading the ROM again so that
+ * changes to it are picked up
+ */
+fn reset(chip8: &mut Chip8, rom_path: &Path) -> Result<(), String> {
+    let rom = fs::read(rom_path).map_err(|e| e.to_string())?;
+    chip8.reset();
+    chip8.load_rom(&rom).map_err(|e| e.to_string())
+}
+
+/**
+ * Run one frame's worth of instructions and tick the timers. Returns
+ * whether the screen needs redrawing. If the debugger pauses the program
+ * partway through, `mid_frame` is set and the next call carries on with
+ * the rest of the frame instead of starting another
+ */
+fn run_frame(
+    chip8: &mut Chip8,
+    debugger: &mut Option<Debugger>,
+    mid_frame: &mut bool,
+) -> Result<bool, Error> {
+    let mut should_draw = false;
+
+    if !*mid_frame {
+        chip8.start_frame();
+        *mid_frame = true;
+    }
+    loop {
+        // While paused in the debugger the window keeps rendering,
+        // but no instructions are executed
+        if let Some(debugger) = debugger.as_mut() {
+            if !debugger.can_run() {
+                return Ok(should_draw);
+            }
+        }
+        if !chip8.frame_cycles_left() {
+            break;
+        }
+
+        chip8.emulate_cycle()?;
+
+        if let Some(debugger) = debugger.as_mut() {
+            debugger.after_cycle(chip8);
+        }
+
+        // If any of the cpu cycles in this frame requested to draw,
+        // set the flag in the event loop
+        if chip8.draw_flag {
+            should_draw = true
+        }
+    }
+
+    // Timers tick once per frame, however many instructions were run
+    chip8.vertical_blank();
+    *mid_frame = false;
+
+    Ok(should_draw)
+}
+
+fn window_scale(scale: u8) -> Result<Scale, String> {
+    match scale {
+        1 => Ok(Scale::X1),
+        2 => Ok(Scale::X2),
+        4 => Ok(Scale::X4),
+        8 => Ok(Scale::X8),
+        16 => Ok(Scale::X16),
+        32 => Ok(Scale::X32),
+        _ => Err(format!(
+            "Unsupported window scale {}, expected 1, 2, 4, 8, 16 or 32",
+            scale
+        )),
+    }
+}
@@ -0,0 +1,71 @@
+use super::osd::Osd;
+use crate::screenshot;
+use crab_chip::{Chip8, Movie};
+use std::fs;
+use std::path::Path;
+
+/**
+ * An input movie being recorded from, or played back into, the window
+ */
+pub enum MovieMode {
+    Recording(Movie),
+    Playing { movie: Movie, frame: usize },
+}
+
+impl MovieMode {
+    /**
+     * Set the keypad for the frame about to be run. Recordings take the
+     * keys held in the window, and playback replaces them with the
+     * movie's. Returns false once playback has reached the end
+     */
+    pub fn next_frame(&mut self, chip8: &mut Chip8, held_keys: &[bool]) -> bool {
+        match self {
+            MovieMode::Recording(_) => {
+                chip8.set_keys(held_keys);
+                true
+            }
+            MovieMode::Playing { movie, frame } => {
+                *frame += 1;
+                movie.play_frame(*frame - 1, chip8)
+            }
+        }
+    }
+
+    /**
+     * Record the keys held through a frame once it has finished running
+     */
+    pub fn end_frame(&mut self, chip8: &Chip8) {
+        if let MovieMode::Recording(movie) = self {
+            movie.record_frame(chip8);
+        }
+    }
+
+    /**
+     * Stop recording or playing. Recordings are saved next to the ROM
+     */
+    pub fn finish(self, rom_path: &Path, osd: &mut Osd) {
+        let movie = match self {
+            MovieMode::Recording(movie) => movie,
+            MovieMode::Playing { movie, frame } => {
+                let message = match frame > movie.len() {
+                    true => "MOVIE FINISHED",
+                    false => "MOVIE STOPPED",
+                };
+                osd.show(message.to_string());
+                return;
+            }
+        };
+
+        let path = screenshot::timestamped_path(rom_path, "c8m");
+        match fs::write(&path, movie.to_bytes()) {
+            Ok(()) => {
+                println!("Saved movie to {}", path.display());
+                osd.show("MOVIE SAVED".to_string());
+            }
+            Err(e) => {
+                eprintln!("Can't save movie {}: {}", path.display(), e);
+                osd.show("MOVIE FAILED".to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use super::text;
+use crab_chip::TARGET_FPS;
+
+const FOREGROUND: u32 = 0x00_FFFFFF;
+const BACKGROUND: u32 = 0x00_000000;
+
+// How long a message stays on screen
+const MESSAGE_FRAMES: u64 = 2 * TARGET_FPS;
+
+/**
+ * On-screen display for short status messages, drawn over the top left
+ * corner of the game
+ */
+#[derive(Default)]
+pub struct Osd {
+    message: String,
+    frames_left: u64,
+}
+
+impl Osd {
+    pub fn show(&mut self, message: String) {
+        self.message = message;
+        self.frames_left = MESSAGE_FRAMES;
+    }
+
+    /**
+     * Count down the current message. Returns true while one is showing
+     * and on the frame it disappears, since the game needs redrawing
+     * underneath it either way
+     */
+    pub fn update(&mut self) -> bool {
+        if self.frames_left == 0 {
+            return false;
+        }
+        self.frames_left -= 1;
+        true
+    }
+
+    pub fn draw(&self, framebuffer: &mut [u32], width: usize) {
+        if self.frames_left == 0 {
+            return;
+        }
+
+        // Black out a box behind the text so it can be read over any game
+        let box_width = (self.message.chars().count() * text::CHAR_WIDTH + 1).min(width);
+        for row in framebuffer.chunks_mut(width).take(text::LINE_HEIGHT + 1) {
+            row[..box_width]
+                .iter_mut()
+                .for_each(|pixel| *pixel = BACKGROUND);
+        }
+
+        text::draw(framebuffer, width, 1, 1, &self.message, FOREGROUND);
+    }
+}
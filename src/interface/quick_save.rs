@@ -0,0 +1,64 @@
+use crab_chip::Chip8;
+use minifb::{Key, KeyRepeat, Window};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/**
+ * Quick save slots are mapped to the function keys:
+ *
+ *  F1 ... F9           Load state from slot 1 ... 9
+ *  Shift + F1 ... F9   Save state to slot 1 ... 9
+ *
+ * States are written next to the ROM, e.g. `roms/Pong.state1`
+ */
+const SLOT_KEYS: [Key; 9] = [
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+];
+
+/**
+ * Returns whether a state was loaded
+ */
+pub fn handle_hotkeys(window: &Window, chip8: &mut Chip8, rom_path: &Path) -> bool {
+    let mut loaded = false;
+    let shift_held = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+
+    for (i, key) in SLOT_KEYS.iter().enumerate() {
+        if !window.is_key_pressed(*key, KeyRepeat::No) {
+            continue;
+        }
+
+        let slot = i + 1;
+        let path = state_path(rom_path, slot);
+
+        // A failed save or load shouldn't end the game, so just report it
+        let result = match shift_held {
+            true => fs::write(&path, chip8.save_state()).map_err(|e| e.to_string()),
+            false => fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|state| chip8.load_state(&state).map_err(|e| e.to_string())),
+        };
+
+        match (result, shift_held) {
+            (Ok(_), true) => println!("Saved state to slot {}", slot),
+            (Ok(_), false) => {
+                println!("Loaded state from slot {}", slot);
+                loaded = true;
+            }
+            (Err(e), _) => eprintln!("Quick save slot {} ({}): {}", slot, path.display(), e),
+        }
+    }
+
+    loaded
+}
+
+fn state_path(rom_path: &Path, slot: usize) -> PathBuf {
+    rom_path.with_extension(format!("state{}", slot))
+}
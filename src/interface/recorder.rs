@@ -0,0 +1,157 @@
+use super::sound::{Synth, SAMPLE_RATE};
+use crate::theme::Palette;
+use crate::Config;
+use crab_chip::{Chip8, HIRES_VIDEO_HEIGHT, HIRES_VIDEO_WIDTH, TARGET_FPS};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+type Error = Box<dyn std::error::Error>;
+
+/**
+ * Records every frame shown in the window to an animated GIF, and
+ * optionally the buzzer to a WAV file with the same name
+ */
+pub struct Recorder {
+    gif: gif::Encoder<BufWriter<File>>,
+    path: PathBuf,
+    scale: usize,
+    // The last frame captured, as palette indices. It isn't written until a
+    // different one comes along, since it may be shown for many frames
+    pending: Option<(Vec<u8>, Palette)>,
+    // Frames captured so far
+    frames: u64,
+    // Length of the frames written so far, in the GIF's hundredths of a second
+    written_centiseconds: u64,
+    wav: Option<(hound::WavWriter<BufWriter<File>>, Synth)>,
+}
+
+impl Recorder {
+    pub fn start(path: &Path, settings: &Config) -> Result<Self, Error> {
+        // Low resolution games are scaled up to match the window, so that
+        // the size doesn't change when a game switches resolution
+        let scale = settings.screenshot_scale.max(1);
+        let width = (HIRES_VIDEO_WIDTH * scale) as u16;
+        let height = (HIRES_VIDEO_HEIGHT * scale) as u16;
+
+        let mut gif = gif::Encoder::new(BufWriter::new(File::create(path)?), width, height, &[])?;
+        gif.set_repeat(gif::Repeat::Infinite)?;
+
+        let wav = match settings.record_audio {
+            true => {
+                let spec = hound::WavSpec {
+                    channels: 1,
+                    sample_rate: SAMPLE_RATE,
+                    bits_per_sample: 16,
+                    sample_format: hound::SampleFormat::Int,
+                };
+                let synth = Synth::new(
+                    settings.sound_frequency,
+                    settings.sound_waveform,
+                    settings.sound_volume,
+                );
+                Some((
+                    hound::WavWriter::create(path.with_extension("wav"), spec)?,
+                    synth,
+                ))
+            }
+            false => None,
+        };
+
+        Ok(Recorder {
+            gif,
+            path: path.to_path_buf(),
+            scale,
+            pending: None,
+            frames: 0,
+            written_centiseconds: 0,
+            wav,
+        })
+    }
+
+    /**
+     * Add the current screen, and a frame's worth of audio, to the recording.
+     * Called once per frame
+     */
+    pub fn capture(
+        &mut self,
+        chip8: &Chip8,
+        palette: Palette,
+        sound_active: bool,
+    ) -> Result<(), Error> {
+        let width = HIRES_VIDEO_WIDTH * self.scale;
+        let scale_x = width / chip8.display_width();
+        let scale_y = HIRES_VIDEO_HEIGHT * self.scale / chip8.display_height();
+
+        let pixels: Vec<u8> = (0..width * HIRES_VIDEO_HEIGHT * self.scale)
+            .map(|i| {
+                let x = (i % width) / scale_x;
+                let y = (i / width) / scale_y;
+                chip8.framebuffer()[y * chip8.display_width() + x] & 0b11
+            })
+            .collect();
+
+        // Most frames are the same as the one before, which just makes the
+        // one before last longer
+        let frame = (pixels, palette);
+        if self.pending.as_ref() != Some(&frame) {
+            self.write_pending()?;
+            self.pending = Some(frame);
+        }
+        self.frames += 1;
+
+        if let Some((wav, synth)) = self.wav.as_mut() {
+            synth.update(sound_active, chip8.audio_pattern());
+            for _ in 0..SAMPLE_RATE as u64 / TARGET_FPS {
+                wav.write_sample((synth.next_sample() * i16::MAX as f32) as i16)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Write out the rest of the recording. Returns the GIF's path
+     */
+    pub fn finish(mut self) -> Result<PathBuf, Error> {
+        self.write_pending()?;
+        if let Some((wav, _)) = self.wav.take() {
+            wav.finalize()?;
+        }
+        // The GIF is finished off when its encoder is dropped
+        Ok(self.path)
+    }
+
+    fn write_pending(&mut self) -> Result<(), Error> {
+        let (pixels, palette) = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+
+        // GIF frame delays are in hundredths of a second, which 60 fps doesn't
+        // divide evenly, so each delay is rounded to keep the total in step
+        let end = (self.frames * 100 + TARGET_FPS / 2) / TARGET_FPS;
+        let delay = end - self.written_centiseconds;
+        self.written_centiseconds = end;
+
+        let colours: Vec<u8> = palette
+            .iter()
+            .flat_map(|colour| {
+                let [_, r, g, b] = colour.to_be_bytes();
+                [r, g, b]
+            })
+            .collect();
+
+        let frame = gif::Frame {
+            delay: delay.min(u16::MAX as u64) as u16,
+            width: (HIRES_VIDEO_WIDTH * self.scale) as u16,
+            height: (HIRES_VIDEO_HEIGHT * self.scale) as u16,
+            palette: Some(colours),
+            buffer: pixels.into(),
+            ..gif::Frame::default()
+        };
+        self.gif.write_frame(&frame)?;
+
+        Ok(())
+    }
+}
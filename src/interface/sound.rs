@@ -1,15 +1,193 @@
-use rodio::source::{SineWave, Source};
+use rodio::source::Source;
 use rodio::{OutputStream, Sink};
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-pub fn beep() {
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let sink = Sink::try_new(&stream_handle).unwrap();
+pub const SAMPLE_RATE: u32 = 44100;
 
-    let source = SineWave::new(440)
-        .take_duration(Duration::from_secs_f32(0.15))
-        .amplify(0.10);
-    sink.append(source);
+// How many samples are played between checks for a change in what to play,
+// about 6 ms. Less often than every sample, so the emulator thread isn't
+// competing with the audio thread for the lock all the time
+const SAMPLES_PER_UPDATE: u32 = 256;
 
-    sink.sleep_until_end();
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /**
+     * The waveform's value at `phase`, from 0 to 1 through one period
+     */
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => match phase < 0.5 {
+                true => 1.0,
+                false => -1.0,
+            },
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+        }
+    }
+}
+
+/**
+ * What the emulator wants played, shared with the audio thread
+ */
+#[derive(Clone, Copy, Default)]
+struct Voice {
+    active: bool,
+    // XO-CHIP audio pattern and its playback rate, replacing the plain tone
+    pattern: Option<([u8; 16], f32)>,
+}
+
+/**
+ * A single audio stream that stays open for as long as the emulator runs
+ * and plays the buzzer whenever it's told to
+ */
+pub struct Audio {
+    voice: Arc<Mutex<Voice>>,
+    // Nothing is heard once these are dropped
+    _stream: OutputStream,
+    _sink: Sink,
+}
+
+impl Audio {
+    pub fn new(frequency: f32, waveform: Waveform, volume: f32) -> Result<Self, String> {
+        let (stream, stream_handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
+        let sink = Sink::try_new(&stream_handle).map_err(|e| e.to_string())?;
+
+        let voice = Arc::new(Mutex::new(Voice::default()));
+
+        sink.append(Tone {
+            voice: Arc::clone(&voice),
+            synth: Synth::new(frequency, waveform, volume),
+            samples_until_update: 0,
+        });
+
+        Ok(Audio {
+            voice,
+            _stream: stream,
+            _sink: sink,
+        })
+    }
+
+    /**
+     * Start or stop the buzzer. Called once per frame
+     */
+    pub fn update(&self, active: bool, pattern: Option<([u8; 16], f32)>) {
+        if let Ok(mut voice) = self.voice.lock() {
+            *voice = Voice { active, pattern };
+        }
+    }
+}
+
+/**
+ * Generates the buzzer's samples, for playing or for recording
+ */
+pub struct Synth {
+    current: Voice,
+    frequency: f32,
+    waveform: Waveform,
+    volume: f32,
+    // Position through the current period of the waveform, or through the
+    // whole XO-CHIP pattern, from 0 to 1
+    phase: f32,
+}
+
+impl Synth {
+    pub fn new(frequency: f32, waveform: Waveform, volume: f32) -> Self {
+        Synth {
+            current: Voice::default(),
+            frequency,
+            waveform,
+            volume: volume.clamp(0.0, 1.0),
+            phase: 0.0,
+        }
+    }
+
+    /**
+     * Start or stop the buzzer, for the samples that follow
+     */
+    pub fn update(&mut self, active: bool, pattern: Option<([u8; 16], f32)>) {
+        self.play(Voice { active, pattern });
+    }
+
+    fn play(&mut self, voice: Voice) {
+        // Each beep starts at the beginning of the waveform
+        if voice.active && !self.current.active {
+            self.phase = 0.0;
+        }
+        self.current = voice;
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        if !self.current.active {
+            return 0.0;
+        }
+
+        let sample = match self.current.pattern {
+            // XO-CHIP patterns are 128 1-bit samples, played back at `rate` Hz
+            Some((pattern, rate)) => {
+                let bit = (self.phase * 128.0) as usize % 128;
+                self.phase = (self.phase + rate / 128.0 / SAMPLE_RATE as f32).fract();
+                match (pattern[bit / 8] >> (7 - bit % 8)) & 0x01 {
+                    1 => 1.0,
+                    _ => -1.0,
+                }
+            }
+            None => {
+                let sample = self.waveform.sample(self.phase);
+                self.phase = (self.phase + self.frequency / SAMPLE_RATE as f32).fract();
+                sample
+            }
+        };
+
+        sample * self.volume
+    }
+}
+
+/**
+ * Endless audio source that is silent unless the shared `Voice` is active
+ */
+struct Tone {
+    voice: Arc<Mutex<Voice>>,
+    synth: Synth,
+    samples_until_update: u32,
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.samples_until_update == 0 {
+            // Keep playing what we have rather than wait on the emulator
+            if let Ok(voice) = self.voice.try_lock() {
+                self.synth.play(*voice);
+            }
+            self.samples_until_update = SAMPLES_PER_UPDATE;
+        }
+        self.samples_until_update -= 1;
+
+        Some(self.synth.next_sample())
+    }
+}
+
+impl Source for Tone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        1
+    }
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
 }
@@ -0,0 +1,36 @@
+//! CHIP-8, SUPER-CHIP and XO-CHIP emulator core.
+//!
+//! The `crab-chip` executable is one frontend for it, but nothing here
+//! depends on a window or an audio device, so a `Chip8` can be embedded
+//! and driven directly:
+//!
+//! ```no_run
+//! use crab_chip::{Chip8, MemorySize, Quirks};
+//!
+//! let rom = std::fs::read("roms/Pong.ch8").unwrap();
+//! let mut chip8 = Chip8::new(Quirks::default(), MemorySize::Standard);
+//! chip8.initialize();
+//! chip8.load_rom(&rom).unwrap();
+//!
+//! chip8.set_key(0x1, true);
+//! for _ in 0..60 {
+//!     chip8.run_frame().unwrap();
+//! }
+//! let pixels = chip8.framebuffer();
+//! ```
+pub mod assembler;
+mod core;
+pub mod disassembler;
+pub use crate::core::{
+    AccessKind, Chip8, Error, Instruction, MemoryAccess, MemorySize, Movie, Quirks, QuirksPreset,
+    RandomMode, RandomSource, RewindBuffer, StdRandom, Syntax, Timing, VipRandom, MEMORY_SIZE,
+    PC_START_ADDRESS, XO_CHIP_MEMORY_SIZE,
+};
+
+pub const VIDEO_WIDTH: usize = 64;
+pub const VIDEO_HEIGHT: usize = 32;
+pub const HIRES_VIDEO_WIDTH: usize = 128;
+pub const HIRES_VIDEO_HEIGHT: usize = 64;
+// Default clock speed, see `Chip8::set_clock_speed`
+pub const CLOCK_SPEED_HZ: u64 = 600;
+pub const TARGET_FPS: u64 = 60;
@@ -1,34 +1,344 @@
 // #![windows_subsystem = "windows"]
-mod core;
+mod cli;
+mod database;
+mod headless;
 mod interface;
-mod utils;
-use crate::core::Chip8;
-use serde;
-use serde_yaml;
+mod screenshot;
+mod theme;
+use crab_chip::{assembler, disassembler};
+use crab_chip::{
+    Chip8, MemorySize, Movie, QuirksPreset, RandomMode, Syntax, Timing, CLOCK_SPEED_HZ,
+};
+use database::{Database, RomInfo};
+use interface::{Bindings, Hotkeys, Waveform};
+use std::collections::HashMap;
+use std::fs;
 use std::fs::File;
+use std::path::{Path, PathBuf};
+use theme::{PaletteConfig, Themes};
 
-const VIDEO_WIDTH: usize = 64;
-const VIDEO_HEIGHT: usize = 32;
-const CLOCK_SPEED_HZ: u64 = 600;
-const TARGET_FPS: u64 = 60;
-
-const DEBUG: bool = false;
+const ROM_DIRECTORY: &str = "roms";
+const CONFIG_FILE_NAME: &str = "settings.yaml";
 
 #[derive(serde::Deserialize, Debug)]
+#[serde(default)]
 pub struct Config {
-    rom_name: String,
+    rom_name: Option<String>,
     sound_enabled: bool,
+    sound_frequency: f32,
+    sound_waveform: Waveform,
+    sound_volume: f32,
+    quirks: QuirksPreset,
+    extended_memory: bool,
+    rewind_seconds: u64,
+    debugger: bool,
+    clock_hz: u64,
+    timing: Timing,
+    scale: u8,
+    theme: String,
+    palette: Option<PaletteConfig>,
+    keys: Bindings,
+    // Per-game controls, keyed by the SHA-1 of the ROM
+    rom_keys: HashMap<String, Bindings>,
+    hotkeys: Hotkeys,
+    turbo_speed: u64,
+    screenshot_scale: usize,
+    record_audio: bool,
+    random: RandomMode,
+    // Same seed, same random numbers. Chosen afresh every run when `None`
+    seed: Option<u64>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rom_name: None,
+            sound_enabled: true,
+            sound_frequency: 440.0,
+            sound_waveform: Waveform::Square,
+            sound_volume: 0.1,
+            quirks: QuirksPreset::default(),
+            extended_memory: false,
+            rewind_seconds: 10,
+            debugger: false,
+            clock_hz: CLOCK_SPEED_HZ,
+            timing: Timing::default(),
+            scale: 8,
+            theme: "mono".to_string(),
+            palette: None,
+            keys: Bindings::default(),
+            rom_keys: HashMap::new(),
+            hotkeys: Hotkeys::default(),
+            turbo_speed: 4,
+            screenshot_scale: 1,
+            record_audio: true,
+            random: RandomMode::default(),
+            seed: None,
+        }
+    }
+}
+
+impl Config {
+    /**
+     * Use the platform, speed and colours that the ROM database has for the game
+     */
+    fn apply_rom_info(&mut self, rom_info: &RomInfo) {
+        if let Some(preset) = rom_info.quirks_preset() {
+            self.quirks = preset;
+        }
+        if rom_info.extended_memory() {
+            self.extended_memory = true;
+        }
+        if let Some(clock_hz) = rom_info.clock_hz() {
+            self.clock_hz = clock_hz;
+        }
+        // A palette chosen by the player is kept over the game's own
+        if self.palette.is_none() {
+            self.palette = rom_info.palette();
+        }
+    }
+    /**
+     * Override settings with any that were given on the command line
+     */
+    fn apply(&mut self, args: &cli::Args) {
+        if let Some(clock_hz) = args.clock_hz {
+            self.clock_hz = clock_hz;
+        }
+        if let Some(scale) = args.scale {
+            self.scale = scale;
+        }
+        if let Some(screenshot_scale) = args.screenshot_scale {
+            self.screenshot_scale = screenshot_scale;
+        }
+        if let Some(seed) = args.seed {
+            self.seed = Some(seed);
+        }
+        if let Some(quirks) = args.quirks {
+            self.quirks = quirks;
+        }
+        if args.extended_memory {
+            self.extended_memory = true;
+        }
+        if args.mute {
+            self.sound_enabled = false;
+        }
+    }
+}
+
+fn main() {
+    // Print errors for people rather than with `Debug`, as returning them from main would
+    if let Err(e) = run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("disasm") => return disassemble(&args[1..]),
+        Some("asm") => return assemble(&args[1..]),
+        _ => {}
+    }
+
+    let args = cli::parse(&args)?;
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+
+    let config_path = match &args.config_path {
+        Some(path) => Some(path.clone()),
+        None => find_config(),
+    };
+
+    let mut settings: Config = match &config_path {
+        Some(path) => {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            serde_yaml::from_reader(file).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => Config::default(),
+    };
+
+    let rom_path = match (&args.rom_path, &settings.rom_name) {
+        (Some(path), _) => path.clone(),
+        // ROMs named in the settings file are looked up in the roms
+        // folder next to it, unless the name is an absolute path
+        (None, Some(rom_name)) => config_path
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or_else(|| Path::new(""))
+            .join(ROM_DIRECTORY)
+            .join(rom_name),
+        (None, None) => return Err(format!("No ROM given\n\n{}", cli::USAGE).into()),
+    };
+
+    let rom = fs::read(&rom_path).map_err(|e| format!("{}: {}", rom_path.display(), e))?;
+
+    // Settings for this particular game take precedence over the settings
+    // file, but not over the command line
+    let database = Database::load(config_path.as_deref().and_then(Path::parent))?;
+    let rom_info = database.find(&sha1_smol::Sha1::from(&rom).digest().to_string());
+    if let Some(rom_info) = &rom_info {
+        settings.apply_rom_info(rom_info);
+    }
+    settings.apply(&args);
+
+    let memory_size = match settings.extended_memory {
+        true => MemorySize::Extended,
+        false => MemorySize::Standard,
+    };
+
+    let mut quirks = settings.quirks.quirks();
+    // The game's quirks are for its own platform, which `--quirks` replaces
+    if let (Some(rom_info), None) = (&rom_info, args.quirks) {
+        rom_info.adjust_quirks(&mut quirks);
+    }
+
+    let movie = match &args.movie_path {
+        Some(path) => {
+            let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            Some(Movie::from_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))?)
+        }
+        None => None,
+    };
+
+    // Movies are played back on the same machine they were recorded on
+    let chip8 = match &movie {
+        Some(movie) => movie.start(&rom)?,
+        None => {
+            let mut chip8 = Chip8::new(quirks, memory_size);
+            chip8.set_clock_speed(settings.clock_hz);
+            chip8.set_timing(settings.timing);
+            chip8.set_random_mode(settings.random);
+            // Headless runs are repeatable unless asked otherwise
+            let seed = match args.headless {
+                true => settings.seed.or(Some(0)),
+                false => settings.seed,
+            };
+            if let Some(seed) = seed {
+                chip8.set_rng_seed(seed);
+            }
+            chip8.initialize();
+            chip8.load_rom(&rom)?;
+            chip8
+        }
+    };
+
+    let themes = Themes::new(&settings.theme, settings.palette)?;
+
+    match args.headless {
+        true => headless::run(
+            chip8,
+            &args.headless_options,
+            themes.palette(),
+            settings.screenshot_scale,
+            movie.as_ref(),
+        )?,
+        false => interface::render(
+            chip8,
+            &settings,
+            &rom_path,
+            themes,
+            rom_info.as_ref(),
+            movie,
+        )?,
+    }
+
+    Ok(())
+}
+
+/**
+ * Find the settings file, either in the current directory or in the
+ * user's config directory
+ */
+fn find_config() -> Option<PathBuf> {
+    let local = PathBuf::from(CONFIG_FILE_NAME);
+    if local.is_file() {
+        return Some(local);
+    }
+
+    let user = config_directory()?.join("crab-chip").join(CONFIG_FILE_NAME);
+    match user.is_file() {
+        true => Some(user),
+        false => None,
+    }
+}
+
+/**
+ * `$XDG_CONFIG_HOME`, falling back to `~/.config`, or `%APPDATA%` on Windows
+ */
+fn config_directory() -> Option<PathBuf> {
+    let from_env = |name| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+
+    if cfg!(windows) {
+        return from_env("APPDATA");
+    }
+
+    from_env("XDG_CONFIG_HOME").or_else(|| from_env("HOME").map(|home| home.join(".config")))
+}
+
+/**
+ * `crab-chip disasm ROM [--octo]`
+ *
+ * Print a disassembly of ROM, in Cowgod's syntax unless `--octo` is given
+ */
+fn disassemble(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut syntax = Syntax::Cowgod;
+    let mut rom_path = None;
+
+    for arg in args {
+        match arg.as_str() {
+            "--octo" => syntax = Syntax::Octo,
+            path => rom_path = Some(path),
+        }
+    }
+
+    let rom_path = rom_path.ok_or("Usage: crab-chip disasm ROM [--octo]")?;
+    let rom = fs::read(rom_path)?;
+
+    print!("{}", disassembler::disassemble(&rom, syntax));
+
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let settings: Config = serde_yaml::from_reader(File::open("settings.yaml")?)?;
+/**
+ * `crab-chip asm SOURCE [-o OUTPUT]`
+ *
+ * Assemble SOURCE into a ROM, written to OUTPUT or SOURCE with a .ch8 extension
+ */
+fn assemble(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let usage = "Usage: crab-chip asm SOURCE|- [-o OUTPUT]";
+    let mut source_path = None;
+    let mut output_path = None;
 
-    let mut chip8 = Chip8::new();
-    chip8.initialize();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output_path = Some(args.next().ok_or(usage)?.as_str()),
+            path => source_path = Some(path),
+        }
+    }
 
-    chip8.load_game(&settings.rom_name)?;
+    let source_path = source_path.ok_or(usage)?;
+    let output_path = match (output_path, source_path) {
+        (Some(path), _) => Path::new(path).to_path_buf(),
+        (None, "-") => return Err("`-o OUTPUT` is needed to assemble standard input".into()),
+        (None, path) => Path::new(path).with_extension("ch8"),
+    };
 
-    interface::render(chip8, &settings)?;
+    // `-` reads the source from standard input, e.g. piped from `disasm`
+    let source = match source_path {
+        "-" => std::io::read_to_string(std::io::stdin())?,
+        path => fs::read_to_string(path)?,
+    };
+    let rom = assembler::assemble(&source).map_err(|e| format!("{}: {}", source_path, e))?;
+    fs::write(&output_path, rom)?;
 
     Ok(())
 }
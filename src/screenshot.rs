@@ -0,0 +1,105 @@
+use crate::theme::Palette;
+use crab_chip::Chip8;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/**
+ * Save the screen as a PNG in the given palette, with each pixel drawn
+ * as a `scale` x `scale` square
+ */
+pub fn write_png(
+    path: &Path,
+    chip8: &Chip8,
+    palette: Palette,
+    scale: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scale = scale.max(1);
+    let width = chip8.display_width();
+    let height = chip8.display_height();
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        (width * scale) as u32,
+        (height * scale) as u32,
+    );
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut pixels = Vec::with_capacity(width * height * scale * scale * 3);
+    for row in chip8.framebuffer().chunks(width) {
+        let scaled_row: Vec<u8> = row
+            .iter()
+            .flat_map(|planes| {
+                let [_, r, g, b] = palette[*planes as usize & 0b11].to_be_bytes();
+                [r, g, b].repeat(scale)
+            })
+            .collect();
+        for _ in 0..scale {
+            pixels.extend_from_slice(&scaled_row);
+        }
+    }
+
+    encoder.write_header()?.write_image_data(&pixels)?;
+
+    Ok(())
+}
+
+/**
+ * A path for a new screenshot or recording next to the ROM, named after
+ * the ROM and the current (UTC) time, e.g. `roms/Pong-2021-03-14-152653.png`
+ */
+pub fn timestamped_path(rom_path: &Path, extension: &str) -> PathBuf {
+    let stem = rom_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("screenshot");
+
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_date(seconds / 86400);
+    let time = seconds % 86400;
+    let name = format!(
+        "{}-{:04}-{:02}-{:02}-{:02}{:02}{:02}",
+        stem,
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    );
+
+    // Don't overwrite a file saved earlier in the same second
+    let mut path = rom_path.with_file_name(format!("{}.{}", name, extension));
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = rom_path.with_file_name(format!("{}-{}.{}", name, count, extension));
+    }
+    path
+}
+
+/**
+ * Year, month and day of the given number of days since 1970-01-01,
+ * from Howard Hinnant's `civil_from_days`
+ */
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = match shifted_month < 10 {
+        true => shifted_month + 3,
+        false => shifted_month - 9,
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
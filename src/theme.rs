@@ -0,0 +1,138 @@
+use std::convert::TryFrom;
+
+/**
+ * Colours for each combination of the two XO-CHIP bitplanes:
+ * neither (the background), plane 1, plane 2, both
+ */
+pub type Palette = [u32; 4];
+
+const BUILT_IN_THEMES: [(&str, Palette); 5] = [
+    ("mono", [0x00_000000, 0x00_FFFFFF, 0x00_AAAAAA, 0x00_555555]),
+    // Green phosphor CRT
+    (
+        "green",
+        [0x00_0A1A0A, 0x00_33FF33, 0x00_1F8F1F, 0x00_B0FFB0],
+    ),
+    // Amber phosphor CRT
+    (
+        "amber",
+        [0x00_1A0F00, 0x00_FFB000, 0x00_A86800, 0x00_FFE0A0],
+    ),
+    // Original Game Boy LCD
+    ("lcd", [0x00_9BBC0F, 0x00_0F380F, 0x00_8BAC0F, 0x00_306230]),
+    // Octo's default colours
+    ("octo", [0x00_996600, 0x00_FFCC00, 0x00_FF6600, 0x00_662200]),
+];
+
+/**
+ * The `palette` section of the settings file. Any colour left out is
+ * taken from the selected theme
+ */
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct PaletteConfig {
+    background: Option<Colour>,
+    foreground: Option<Colour>,
+    // Pixels drawn only on XO-CHIP's second plane
+    foreground2: Option<Colour>,
+    // Pixels drawn on both XO-CHIP planes
+    blend: Option<Colour>,
+}
+
+impl PaletteConfig {
+    /**
+     * Colours in palette order, any beyond the first four being ignored
+     */
+    pub fn from_colours(colours: &[Colour]) -> Self {
+        let colour = |i: usize| colours.get(i).copied();
+        PaletteConfig {
+            background: colour(0),
+            foreground: colour(1),
+            foreground2: colour(2),
+            blend: colour(3),
+        }
+    }
+}
+
+/**
+ * A colour written as `#RRGGBB`
+ */
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(try_from = "String")]
+pub struct Colour(u32);
+
+impl TryFrom<String> for Colour {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let hex = text.trim_start_matches('#');
+        match (hex.len(), u32::from_str_radix(hex, 16)) {
+            (6, Ok(rgb)) => Ok(Colour(rgb)),
+            _ => Err(format!("Invalid colour `{}`, expected #RRGGBB", text)),
+        }
+    }
+}
+
+/**
+ * The themes that can be cycled through while playing
+ */
+pub struct Themes {
+    themes: Vec<(String, Palette)>,
+    current: usize,
+}
+
+impl Themes {
+    /**
+     * Start on the built-in theme called `name`. When a custom palette is
+     * given, it's based on that theme and shown first instead
+     */
+    pub fn new(name: &str, palette: Option<PaletteConfig>) -> Result<Self, String> {
+        let mut themes: Vec<(String, Palette)> = BUILT_IN_THEMES
+            .iter()
+            .map(|(name, palette)| (name.to_string(), *palette))
+            .collect();
+
+        let current = themes
+            .iter()
+            .position(|(theme, _)| theme == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = BUILT_IN_THEMES.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "Unknown theme `{}`, expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+
+        if let Some(custom) = palette {
+            let mut colours = themes[current].1;
+            let overrides = [
+                custom.background,
+                custom.foreground,
+                custom.foreground2,
+                custom.blend,
+            ];
+            for (colour, replacement) in colours.iter_mut().zip(overrides.iter()) {
+                if let Some(Colour(rgb)) = replacement {
+                    *colour = *rgb;
+                }
+            }
+            themes.insert(0, ("custom".to_string(), colours));
+            return Ok(Themes { themes, current: 0 });
+        }
+
+        Ok(Themes { themes, current })
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.themes[self.current].1
+    }
+
+    pub fn name(&self) -> &str {
+        &self.themes[self.current].0
+    }
+
+    pub fn next(&mut self) {
+        self.current = (self.current + 1) % self.themes.len();
+    }
+}
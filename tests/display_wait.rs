@@ -0,0 +1,72 @@
+use crab_chip::{Chip8, MemorySize, Quirks, QuirksPreset, Timing};
+
+// Points I at the font's "0" and draws it at 0, 0 over and over
+const DRAW: [u8; 6] = [0xA0, 0x50, 0xD0, 0x05, 0x12, 0x02];
+
+fn machine(quirks: Quirks, rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks, MemorySize::Standard);
+    chip8.initialize();
+    chip8.load_rom(rom).unwrap();
+    chip8
+}
+
+fn lit_pixels(chip8: &Chip8) -> usize {
+    chip8
+        .framebuffer()
+        .iter()
+        .filter(|pixel| **pixel != 0)
+        .count()
+}
+
+/**
+ * A vertical blank that came before the DXYN was reached doesn't let it
+ * draw; it waits for the next one
+ */
+#[test]
+fn dxyn_waits_for_a_vertical_blank_after_it_was_reached() {
+    let mut chip8 = machine(QuirksPreset::Vip.quirks(), &DRAW);
+    chip8.emulate_cycle().unwrap();
+    chip8.vertical_blank();
+
+    for _ in 0..3 {
+        chip8.emulate_cycle().unwrap();
+        assert_eq!(chip8.program_counter(), 0x202);
+        assert_eq!(lit_pixels(&chip8), 0);
+    }
+
+    chip8.vertical_blank();
+    chip8.emulate_cycle().unwrap();
+    assert_eq!(chip8.program_counter(), 0x204);
+    assert_eq!(lit_pixels(&chip8), 14);
+}
+
+#[test]
+fn dxyn_draws_straight_away_without_the_display_wait_quirk() {
+    let mut chip8 = machine(QuirksPreset::Chip48.quirks(), &DRAW);
+    chip8.emulate_cycle().unwrap();
+    chip8.emulate_cycle().unwrap();
+
+    assert_eq!(chip8.program_counter(), 0x204);
+    assert_eq!(lit_pixels(&chip8), 14);
+}
+
+/**
+ * However fast the clock runs, and with either timing, the display wait
+ * holds a program to one sprite a frame
+ */
+#[test]
+fn the_display_wait_allows_one_sprite_per_frame() {
+    // Counts up in v1 and draws, forever
+    let rom = [0xA0, 0x50, 0x71, 0x01, 0xD0, 0x05, 0x12, 0x02];
+
+    for timing in [Timing::Clock, Timing::Vip] {
+        let mut chip8 = machine(QuirksPreset::Vip.quirks(), &rom);
+        chip8.set_clock_speed(100_000);
+        chip8.set_timing(timing);
+        for _ in 0..10 {
+            chip8.run_frame().unwrap();
+        }
+
+        assert_eq!(chip8.registers()[1], 10, "{:?}", timing);
+    }
+}
@@ -0,0 +1,105 @@
+use crab_chip::{Chip8, MemorySize, Quirks, QuirksPreset};
+
+// Sets v0 to 0xEE, then waits for a key into v0 and loops forever
+const WAIT_FOR_KEY: [u8; 6] = [0x60, 0xEE, 0xF0, 0x0A, 0x12, 0x04];
+
+fn machine(quirks: Quirks) -> Chip8 {
+    let mut chip8 = Chip8::new(quirks, MemorySize::Standard);
+    chip8.initialize();
+    chip8.load_rom(&WAIT_FOR_KEY).unwrap();
+    chip8
+}
+
+fn run_frame(chip8: &mut Chip8) -> u8 {
+    chip8.run_frame().unwrap();
+    chip8.registers()[0]
+}
+
+/**
+ * A key already held down when Fx0A starts waiting doesn't count, even if
+ * it went down earlier in the same frame, until it's pressed again
+ */
+#[test]
+fn fx0a_ignores_keys_pressed_before_the_wait() {
+    let mut chip8 = machine(QuirksPreset::Chip48.quirks());
+
+    chip8.set_key(5, true);
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+
+    chip8.set_key(5, false);
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+
+    chip8.set_key(5, true);
+    assert_eq!(run_frame(&mut chip8), 0x05);
+}
+
+#[test]
+fn fx0a_finishes_on_the_press_without_the_release_quirk() {
+    let mut chip8 = machine(QuirksPreset::Chip48.quirks());
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+
+    chip8.set_key(0xA, true);
+    assert_eq!(run_frame(&mut chip8), 0x0A);
+}
+
+#[test]
+fn fx0a_waits_for_the_release_with_the_release_quirk() {
+    let mut chip8 = machine(QuirksPreset::Vip.quirks());
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+
+    chip8.set_key(7, true);
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+
+    chip8.set_key(7, false);
+    assert_eq!(run_frame(&mut chip8), 0x07);
+}
+
+/**
+ * A key pressed and released again before the next instruction runs
+ * still counts, with or without the release quirk
+ */
+#[test]
+fn fx0a_sees_a_press_and_release_between_cycles() {
+    for preset in [QuirksPreset::Chip48, QuirksPreset::Vip] {
+        let mut chip8 = machine(preset.quirks());
+        assert_eq!(run_frame(&mut chip8), 0xEE);
+
+        chip8.set_key(3, true);
+        chip8.set_key(3, false);
+        assert_eq!(run_frame(&mut chip8), 0x03, "{:?}", preset);
+    }
+}
+
+#[test]
+fn fx0a_takes_the_lowest_of_several_keys_pressed_at_once() {
+    let mut chip8 = machine(QuirksPreset::Chip48.quirks());
+    assert_eq!(run_frame(&mut chip8), 0xEE);
+
+    chip8.set_key(0xC, true);
+    chip8.set_key(0x4, true);
+    assert_eq!(run_frame(&mut chip8), 0x04);
+}
+
+#[test]
+fn presses_and_releases_last_until_the_vertical_blank() {
+    let mut chip8 = machine(QuirksPreset::Chip48.quirks());
+
+    chip8.set_key(2, true);
+    chip8.set_key(9, true);
+    chip8.set_key(9, true);
+    assert_eq!(chip8.key_presses(), 1 << 2 | 1 << 9);
+    assert_eq!(chip8.key_releases(), 0);
+
+    chip8.vertical_blank();
+    assert_eq!(chip8.key_presses(), 0);
+
+    chip8.set_key(2, false);
+    chip8.set_key(4, false);
+    assert_eq!(chip8.key_presses(), 0);
+    assert_eq!(chip8.key_releases(), 1 << 2);
+
+    chip8.vertical_blank();
+    assert_eq!(chip8.key_releases(), 0);
+}
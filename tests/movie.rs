@@ -0,0 +1,93 @@
+use crab_chip::{Chip8, Error, MemorySize, Movie, QuirksPreset, RandomMode, Timing};
+
+// Adds random numbers to v3 while key 0 is held and to v4 otherwise, and
+// waits on Fx0A for key 1 every time round, so the result depends on
+// every key and every random number along the way
+const ROM: [u8; 16] = [
+    0xC0, 0xFF, // 200: v0 = random
+    0xE1, 0xA1, // 202: skip if key v1 (0) isn't held
+    0x83, 0x04, // 204: v3 += v0
+    0xE1, 0x9E, // 206: skip if key v1 (0) is held
+    0x84, 0x04, // 208: v4 += v0
+    0xF5, 0x0A, // 20A: wait for a key, into v5
+    0x76, 0x01, // 20C: v6 += 1
+    0x12, 0x00, // 20E: jump 200
+];
+
+// Which keys are held on each frame of the recording
+fn keys_for(frame: usize) -> [bool; 16] {
+    let mut keys = [false; 16];
+    keys[0] = frame % 7 < 3;
+    keys[1] = frame % 4 == 1;
+    keys
+}
+
+fn record(frames: usize) -> (Movie, Chip8) {
+    let mut chip8 = Chip8::new(QuirksPreset::Vip.quirks(), MemorySize::Standard);
+    chip8.set_clock_speed(900);
+    chip8.set_timing(Timing::Clock);
+    chip8.set_random_mode(RandomMode::Vip);
+    chip8.set_rng_seed(1234);
+    chip8.initialize();
+    chip8.load_rom(&ROM).unwrap();
+
+    let mut movie = Movie::new(&chip8);
+    for frame in 0..frames {
+        chip8.set_keys(&keys_for(frame));
+        chip8.run_frame().unwrap();
+        movie.record_frame(&chip8);
+    }
+    (movie, chip8)
+}
+
+/**
+ * Playing a movie back, after saving and loading it, ends up with the
+ * machine in exactly the state it was in when recording stopped
+ */
+#[test]
+fn playback_matches_the_recording() {
+    let (movie, recorded) = record(120);
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(movie.len(), 120);
+
+    let mut chip8 = movie.start(&ROM).unwrap();
+    let mut frame = 0;
+    while movie.play_frame(frame, &mut chip8) {
+        chip8.run_frame().unwrap();
+        frame += 1;
+    }
+
+    assert_eq!(frame, 120);
+    assert!(recorded.registers()[6] > 0, "Fx0A never finished");
+    assert_eq!(chip8.save_state(), recorded.save_state());
+}
+
+#[test]
+fn movies_only_start_with_their_own_rom() {
+    let (movie, _) = record(10);
+
+    let result = movie.start(&[0x12, 0x00]);
+    assert!(
+        matches!(result, Err(Error::MovieRomMismatch)),
+        "{:?}",
+        result.err()
+    );
+}
+
+#[test]
+fn invalid_movies_are_rejected() {
+    let (movie, _) = record(10);
+    let bytes = movie.to_bytes();
+
+    let mut bad_version = bytes.clone();
+    bad_version[4] = bad_version[4].wrapping_add(1);
+
+    for invalid in [
+        b"XXXX".to_vec(),
+        bad_version,
+        bytes[..bytes.len() - 1].to_vec(),
+    ] {
+        let result = Movie::from_bytes(&invalid);
+        assert!(matches!(result, Err(Error::InvalidMovie)), "{:?}", result);
+    }
+}
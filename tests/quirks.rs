@@ -0,0 +1,89 @@
+use crab_chip::{Chip8, MemorySize, QuirksPreset};
+
+const PRESETS: [QuirksPreset; 5] = [
+    QuirksPreset::Vip,
+    QuirksPreset::Chip48,
+    QuirksPreset::SuperChip,
+    QuirksPreset::XoChip,
+    QuirksPreset::Modern,
+];
+
+fn machine(preset: QuirksPreset, rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new(preset.quirks(), MemorySize::Standard);
+    chip8.initialize();
+    chip8.load_rom(rom).unwrap();
+    chip8
+}
+
+fn run(chip8: &mut Chip8, instructions: usize) {
+    for _ in 0..instructions {
+        chip8.emulate_cycle().unwrap();
+    }
+}
+
+/**
+ * BNNN jumps to NNN plus v0, except on CHIP-48 and SUPER-CHIP, which
+ * read it as BXNN and add vX instead
+ */
+#[test]
+fn bnnn_adds_v0_or_vx() {
+    // v0 = 4, v3 = 8, then B310
+    let rom = [0x60, 0x04, 0x63, 0x08, 0xB3, 0x10];
+
+    for preset in PRESETS {
+        let mut chip8 = machine(preset, &rom);
+        run(&mut chip8, 3);
+
+        let expected = match preset {
+            QuirksPreset::Chip48 | QuirksPreset::SuperChip => 0x318,
+            _ => 0x314,
+        };
+        assert_eq!(chip8.program_counter(), expected, "{:?}", preset);
+    }
+}
+
+/**
+ * A sprite drawn over the right edge of the screen is cut off, except on
+ * XO-CHIP, where the rest of it wraps around to the left edge
+ */
+#[test]
+fn sprites_are_clipped_or_wrapped() {
+    // Draw a row of 8 pixels at 60, 0
+    let rom = [0xA2, 0x08, 0x60, 0x3C, 0x61, 0x00, 0xD0, 0x11, 0xFF];
+
+    for preset in PRESETS {
+        let mut chip8 = machine(preset, &rom);
+        // The VIP only draws once the vertical blank comes
+        while chip8.program_counter() < 0x208 {
+            chip8.emulate_cycle().unwrap();
+            chip8.vertical_blank();
+        }
+
+        let row = &chip8.framebuffer()[..chip8.display_width()];
+        assert!(row[60..].iter().all(|pixel| *pixel != 0), "{:?}", preset);
+        let wrapped = row[..4].iter().all(|pixel| *pixel != 0);
+        assert_eq!(wrapped, preset == QuirksPreset::XoChip, "{:?}", preset);
+    }
+}
+
+/**
+ * Fx55 leaves I past the registers it stored on the VIP and XO-CHIP, one
+ * short of that on CHIP-48, and where it was on SUPER-CHIP
+ */
+#[test]
+fn fx55_moves_i_on_by_the_platforms_amount() {
+    // I = 300, then store v0 to v2
+    let rom = [0xA3, 0x00, 0xF2, 0x55];
+
+    for preset in PRESETS {
+        let mut chip8 = machine(preset, &rom);
+        run(&mut chip8, 2);
+
+        let expected = match preset {
+            QuirksPreset::Chip48 => 0x302,
+            QuirksPreset::SuperChip => 0x300,
+            _ => 0x303,
+        };
+        assert_eq!(chip8.index_register(), expected, "{:?}", preset);
+    }
+}
@@ -0,0 +1,92 @@
+use crab_chip::RewindBuffer;
+
+/**
+ * A stand-in for a save state: mostly unchanged from frame to frame, with
+ * a few bytes that change, so that the deltas have runs of both
+ */
+fn state(frame: u8) -> Vec<u8> {
+    let mut state = vec![0xAA; 64];
+    state[0] = frame;
+    state[10] = frame.wrapping_mul(3);
+    state[11] = 0xAA ^ frame;
+    state[63] = frame;
+    state
+}
+
+#[test]
+fn popping_steps_back_through_every_frame() {
+    let mut rewind = RewindBuffer::new(100);
+    for frame in 0..10 {
+        rewind.push(state(frame));
+    }
+
+    // The latest frame is the one on screen, so the first step goes to the one before it
+    for frame in (0..9).rev() {
+        assert_eq!(rewind.pop(), Some(state(frame)), "frame {}", frame);
+    }
+
+    // The oldest frame stays put once it's reached
+    assert_eq!(rewind.pop(), Some(state(0)));
+}
+
+#[test]
+fn frames_past_the_capacity_are_dropped() {
+    let mut rewind = RewindBuffer::new(4);
+    for frame in 0..10 {
+        rewind.push(state(frame));
+    }
+
+    assert_eq!(rewind.pop(), Some(state(8)));
+    assert_eq!(rewind.pop(), Some(state(7)));
+    assert_eq!(rewind.pop(), Some(state(6)));
+    assert_eq!(rewind.pop(), Some(state(6)));
+}
+
+/**
+ * A state that's a different size from the one after it, like one saved
+ * before switching to high resolution, is kept whole instead of as a delta
+ */
+#[test]
+fn states_of_different_sizes_are_kept_whole() {
+    let mut rewind = RewindBuffer::new(10);
+    let small = vec![0x01, 0x00, 0x00, 0x02];
+    rewind.push(small.clone());
+    rewind.push(state(1));
+    rewind.push(state(2));
+
+    assert_eq!(rewind.pop(), Some(state(1)));
+    assert_eq!(rewind.pop(), Some(small));
+}
+
+#[test]
+fn pushing_after_popping_carries_on_from_there() {
+    let mut rewind = RewindBuffer::new(10);
+    for frame in 0..5 {
+        rewind.push(state(frame));
+    }
+    rewind.pop();
+    rewind.pop();
+    rewind.push(state(20));
+
+    assert_eq!(rewind.pop(), Some(state(2)));
+    assert_eq!(rewind.pop(), Some(state(1)));
+}
+
+#[test]
+fn clearing_forgets_every_frame() {
+    let mut rewind = RewindBuffer::new(10);
+    for frame in 0..5 {
+        rewind.push(state(frame));
+    }
+    rewind.clear();
+
+    assert_eq!(rewind.pop(), None);
+}
+
+#[test]
+fn a_zero_capacity_buffer_keeps_nothing() {
+    let mut rewind = RewindBuffer::new(0);
+    rewind.push(state(1));
+
+    assert_eq!(rewind.pop(), None);
+}
@@ -0,0 +1,52 @@
+use crab_chip::assembler::assemble;
+use crab_chip::disassembler::disassemble;
+use crab_chip::Syntax;
+
+// Every instruction the disassembler knows, reached through a call, a
+// loop and skips, followed by a computed jump and some sprite data
+const ROM: [u8; 112] = [
+    0x00, 0xE0, 0x00, 0xC3, 0x00, 0xD2, 0x00, 0xFB, 0x00, 0xFC, 0x00, 0xFE, //
+    0x00, 0xFF, 0x22, 0x62, 0x30, 0x12, 0x41, 0x34, 0x52, 0x30, 0x94, 0x50, //
+    0x56, 0x72, 0x58, 0x93, 0x6A, 0x0B, 0x7B, 0x01, 0x8C, 0x10, 0x8C, 0x11, //
+    0x8C, 0x12, 0x8C, 0x13, 0x8C, 0x14, 0x8C, 0x15, 0x8C, 0x16, 0x8C, 0x17, //
+    0x8C, 0x1E, 0xA2, 0x6C, 0xC0, 0xFF, 0xD1, 0x25, 0xD1, 0x20, 0xE0, 0x9E, //
+    0xE1, 0xA1, 0xF0, 0x00, 0x02, 0x6C, 0xF2, 0x01, 0xF0, 0x02, 0xF3, 0x07, //
+    0xF4, 0x0A, 0xF5, 0x15, 0xF6, 0x18, 0xF7, 0x1E, 0xF8, 0x29, 0xF9, 0x30, //
+    0xFA, 0x33, 0xFB, 0x3A, 0xFC, 0x55, 0xFD, 0x65, 0xFE, 0x75, 0xFF, 0x85, //
+    0x12, 0x60, 0x30, 0x00, 0x00, 0xEE, 0x31, 0x00, 0x00, 0xFD, 0xB2, 0x6C, //
+    0x3C, 0x42, 0x81, 0x00,
+];
+
+/**
+ * Disassembling a ROM and assembling the listing again, in either
+ * syntax, should give back exactly the same bytes
+ */
+#[test]
+fn disassembly_assembles_to_the_same_rom() {
+    for syntax in [Syntax::Cowgod, Syntax::Octo] {
+        let listing = disassemble(&ROM, syntax);
+        let rom = assemble(&listing).unwrap_or_else(|e| panic!("{:?}: {}", syntax, e));
+        assert_eq!(rom, ROM, "{:?}", syntax);
+    }
+}
+
+/**
+ * Expressions too big for the assembler's arithmetic are errors, not panics
+ */
+#[test]
+fn overflowing_expressions_are_rejected() {
+    for source in [
+        "db 0x7FFFFFFFFFFFFFFF*2",
+        "db 0x7FFFFFFFFFFFFFFF+1",
+        "db (-0x7FFFFFFFFFFFFFFF-1)/-1",
+        "db -(-0x7FFFFFFFFFFFFFFF-1)",
+    ] {
+        let error = assemble(source).expect_err(source);
+        assert!(
+            error.to_string().contains("Expression overflows"),
+            "{}: {}",
+            source,
+            error
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crab_chip::{Chip8, Error, MemorySize, QuirksPreset};
+
+// Draws random sprites at random places, calling a subroutine to do it and
+// counting up in v5, so that most of the machine changes every frame
+const ROM: [u8; 18] = [
+    0xC0, 0x3F, // 200: v0 = random & 0x3F
+    0xC1, 0x1F, // 202: v1 = random & 0x1F
+    0x22, 0x0A, // 204: call 20A
+    0x12, 0x00, // 206: jump 200
+    0xFF, 0x81, // 208: sprite data
+    0xA2, 0x08, // 20A: I = 208
+    0xD0, 0x12, // 20C: draw 2 rows at v0, v1
+    0x75, 0x01, // 20E: v5 += 1
+    0x00, 0xEE, // 210: return
+];
+
+// Where fields sit in a state saved from a 4 KiB, low resolution machine
+const SELECTED_PLANES_OFFSET: usize = 4 + 2 + 20 + 2 + 4 + 4096 + 16 + 2 + 2 + 1;
+const STACK_POINTER_OFFSET: usize = SELECTED_PLANES_OFFSET + 1 + 64 * 32 + 2 + 16 * 2;
+
+fn machine(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::new(QuirksPreset::Chip48.quirks(), MemorySize::Standard);
+    chip8.set_rng_seed(1);
+    chip8.initialize();
+    chip8.load_rom(rom).unwrap();
+    chip8
+}
+
+fn run_frames(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        chip8.run_frame().unwrap();
+    }
+}
+
+/**
+ * Loading a state puts back everything that decides what happens next,
+ * random numbers included, so the game carries on exactly as it did
+ */
+#[test]
+fn loading_a_state_carries_on_from_where_it_was_saved() {
+    let mut chip8 = machine(&ROM);
+    run_frames(&mut chip8, 10);
+    let state = chip8.save_state();
+
+    run_frames(&mut chip8, 10);
+    let expected = chip8.save_state();
+
+    chip8.load_state(&state).unwrap();
+    assert_eq!(chip8.save_state(), state);
+    run_frames(&mut chip8, 10);
+    assert_eq!(chip8.save_state(), expected);
+}
+
+#[test]
+fn states_from_another_rom_are_rejected() {
+    let chip8 = machine(&ROM);
+    let mut other = machine(&[0x12, 0x00]);
+
+    let result = other.load_state(&chip8.save_state());
+    assert!(
+        matches!(result, Err(Error::SaveStateRomMismatch)),
+        "{:?}",
+        result
+    );
+}
+
+/**
+ * Anything that isn't a whole, valid state of the current format is
+ * refused, and the machine is left as it was
+ */
+#[test]
+fn invalid_states_are_rejected() {
+    let mut chip8 = machine(&ROM);
+    run_frames(&mut chip8, 5);
+    let state = chip8.save_state();
+    assert_eq!(state[SELECTED_PLANES_OFFSET], 0b01);
+    assert_eq!(state[STACK_POINTER_OFFSET], chip8.stack_pointer());
+
+    let mut bad_version = state.clone();
+    bad_version[4] = bad_version[4].wrapping_add(1);
+
+    let mut bad_planes = state.clone();
+    bad_planes[SELECTED_PLANES_OFFSET] = 0b100;
+
+    let mut bad_stack_pointer = state.clone();
+    bad_stack_pointer[STACK_POINTER_OFFSET] = 17;
+
+    let invalid = [
+        ("magic", b"XXXX".to_vec()),
+        ("version", bad_version),
+        ("truncated", state[..state.len() - 1].to_vec()),
+        ("selected planes", bad_planes),
+        ("stack pointer", bad_stack_pointer),
+    ];
+
+    run_frames(&mut chip8, 5);
+    let before = chip8.save_state();
+
+    for (name, invalid) in invalid.iter() {
+        let result = chip8.load_state(invalid);
+        assert!(
+            matches!(result, Err(Error::InvalidSaveState)),
+            "{}: {:?}",
+            name,
+            result
+        );
+        assert_eq!(chip8.save_state(), before, "{}", name);
+    }
+}